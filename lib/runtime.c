@@ -1,6 +1,5 @@
 #include <stdio.h>
 #include <string.h>
-#include <err.h>
 #include <stdlib.h>
 
 void printInt(int val) {
@@ -11,8 +10,46 @@ void printString(char* str) {
     printf("%s\n", str);
 }
 
+// Shadow call stack maintained by the compiled code (unless --no-stack-trace): names of the called
+// functions and lines they are executing. Frames beyond MAX_TRACE_DEPTH are counted, not stored.
+#define MAX_TRACE_DEPTH 1024
+static char* trace_functions[MAX_TRACE_DEPTH];
+static int trace_lines[MAX_TRACE_DEPTH];
+static int trace_depth = 0;
+
+void __latc_push_frame(char* function) {
+    if (trace_depth < MAX_TRACE_DEPTH) {
+        trace_functions[trace_depth] = function;
+        trace_lines[trace_depth] = 0;
+    }
+    trace_depth++;
+}
+
+void __latc_pop_frame() {
+    trace_depth--;
+}
+
+void __latc_set_line(int line) {
+    if (trace_depth > 0 && trace_depth <= MAX_TRACE_DEPTH) {
+        trace_lines[trace_depth - 1] = line;
+    }
+}
+
+static void print_stack_trace() {
+    int shown = trace_depth;
+    if (trace_depth > MAX_TRACE_DEPTH) {
+        fprintf(stderr, "  ... %d more frames\n", trace_depth - MAX_TRACE_DEPTH);
+        shown = MAX_TRACE_DEPTH;
+    }
+    for (int i = shown - 1; i >= 0; i--) {
+        fprintf(stderr, "  at %s (line %d)\n", trace_functions[i], trace_lines[i]);
+    }
+}
+
 void error() {
-    errx(1, "runtime error\n");
+    fprintf(stderr, "runtime error\n");
+    print_stack_trace();
+    exit(1);
 }
 
 int readInt() {
@@ -47,3 +84,15 @@ char* __latc_concat_str(char* a, char* b) {
 bool __latc_compare_str(char* a, char* b) {
     return strcmp(a, b) == 0;
 }
+
+// called by checks emitted by the compiler (division by zero, overflow...)
+void __latc_runtime_error(char* msg) {
+    fprintf(stderr, "runtime error: %s\n", msg);
+    print_stack_trace();
+    exit(1);
+}
+
+// zeroed memory for objects
+void* __latc_alloc(long size) {
+    return calloc(1, size);
+}
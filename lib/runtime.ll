@@ -7,6 +7,15 @@ target triple = "x86_64-pc-linux-gnu"
 @.str.2 = private unnamed_addr constant [15 x i8] c"runtime error\0A\00", align 1
 @.str.3 = private unnamed_addr constant [3 x i8] c"%d\00", align 1
 @.str.4 = private unnamed_addr constant [7 x i8] c"%1023s\00", align 1
+%struct._IO_FILE = type opaque
+
+@.str.5 = private unnamed_addr constant [19 x i8] c"runtime error: %s\0A\00", align 1
+@trace_depth = internal unnamed_addr global i32 0, align 4
+@trace_functions = internal unnamed_addr global [1024 x i8*] zeroinitializer, align 16
+@trace_lines = internal unnamed_addr global [1024 x i32] zeroinitializer, align 16
+@stderr = external local_unnamed_addr global %struct._IO_FILE*, align 8
+@.str.6 = private unnamed_addr constant [22 x i8] c"  ... %d more frames\0A\00", align 1
+@.str.7 = private unnamed_addr constant [19 x i8] c"  at %s (line %d)\0A\00", align 1
 
 ; Function Attrs: nounwind uwtable
 define void @printInt(i32) local_unnamed_addr #0 {
@@ -23,14 +32,107 @@ define void @printString(i8* nocapture readonly) local_unnamed_addr #0 {
   ret void
 }
 
+; Function Attrs: norecurse nounwind uwtable
+define void @__latc_push_frame(i8* %function) local_unnamed_addr #0 {
+entry:
+  %depth = load i32, i32* @trace_depth, align 4
+  %has_room = icmp slt i32 %depth, 1024
+  br i1 %has_room, label %record, label %done
+
+record:
+  %idx = sext i32 %depth to i64
+  %function_ptr = getelementptr inbounds [1024 x i8*], [1024 x i8*]* @trace_functions, i64 0, i64 %idx
+  store i8* %function, i8** %function_ptr, align 8
+  %line_ptr = getelementptr inbounds [1024 x i32], [1024 x i32]* @trace_lines, i64 0, i64 %idx
+  store i32 0, i32* %line_ptr, align 4
+  br label %done
+
+done:
+  %new_depth = add nsw i32 %depth, 1
+  store i32 %new_depth, i32* @trace_depth, align 4
+  ret void
+}
+
+; Function Attrs: norecurse nounwind uwtable
+define void @__latc_pop_frame() local_unnamed_addr #0 {
+  %depth = load i32, i32* @trace_depth, align 4
+  %new_depth = add nsw i32 %depth, -1
+  store i32 %new_depth, i32* @trace_depth, align 4
+  ret void
+}
+
+; Function Attrs: norecurse nounwind uwtable
+define void @__latc_set_line(i32 %line) local_unnamed_addr #0 {
+entry:
+  %depth = load i32, i32* @trace_depth, align 4
+  %top = add i32 %depth, -1
+  %in_range = icmp ult i32 %top, 1024
+  br i1 %in_range, label %record, label %done
+
+record:
+  %idx = sext i32 %top to i64
+  %line_ptr = getelementptr inbounds [1024 x i32], [1024 x i32]* @trace_lines, i64 0, i64 %idx
+  store i32 %line, i32* %line_ptr, align 4
+  br label %done
+
+done:
+  ret void
+}
+
 ; Function Attrs: noreturn nounwind uwtable
 define void @error() local_unnamed_addr #2 {
-  tail call void (i32, i8*, ...) @errx(i32 1, i8* getelementptr inbounds ([15 x i8], [15 x i8]* @.str.2, i64 0, i64 0)) #9
+  %err = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8
+  %1 = tail call i64 @fwrite(i8* getelementptr inbounds ([15 x i8], [15 x i8]* @.str.2, i64 0, i64 0), i64 14, i64 1, %struct._IO_FILE* %err) #8
+  tail call fastcc void @print_stack_trace()
+  tail call void @exit(i32 1) #9
   unreachable
 }
 
-; Function Attrs: noreturn
-declare void @errx(i32, i8*, ...) local_unnamed_addr #3
+; Function Attrs: nounwind uwtable
+define internal fastcc void @print_stack_trace() unnamed_addr #0 {
+entry:
+  %depth = load i32, i32* @trace_depth, align 4
+  %has_omitted = icmp sgt i32 %depth, 1024
+  br i1 %has_omitted, label %print_omitted, label %frames
+
+print_omitted:
+  %err = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8
+  %omitted = add nsw i32 %depth, -1024
+  %0 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %err, i8* getelementptr inbounds ([22 x i8], [22 x i8]* @.str.6, i64 0, i64 0), i32 %omitted) #8
+  br label %frames
+
+frames:
+  %shown = select i1 %has_omitted, i32 1024, i32 %depth
+  br label %loop
+
+loop:
+  %i = phi i32 [ %shown, %frames ], [ %prev, %print_frame ]
+  %prev = add nsw i32 %i, -1
+  %more = icmp sgt i32 %i, 0
+  br i1 %more, label %print_frame, label %done
+
+print_frame:
+  %idx = sext i32 %prev to i64
+  %function_ptr = getelementptr inbounds [1024 x i8*], [1024 x i8*]* @trace_functions, i64 0, i64 %idx
+  %function = load i8*, i8** %function_ptr, align 8
+  %line_ptr = getelementptr inbounds [1024 x i32], [1024 x i32]* @trace_lines, i64 0, i64 %idx
+  %line = load i32, i32* %line_ptr, align 4
+  %frame_err = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8
+  %1 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %frame_err, i8* getelementptr inbounds ([19 x i8], [19 x i8]* @.str.7, i64 0, i64 0), i8* %function, i32 %line) #8
+  br label %loop
+
+done:
+  ret void
+}
+
+; Function Attrs: nounwind
+declare i32 @fprintf(%struct._IO_FILE* nocapture, i8* nocapture readonly, ...) local_unnamed_addr #1
+
+; Function Attrs: nounwind
+declare i64 @fwrite(i8* nocapture, i64, i64, %struct._IO_FILE* nocapture) local_unnamed_addr #8
+
+; Function Attrs: noreturn nounwind
+declare void @exit(i32) local_unnamed_addr #3
 
 ; Function Attrs: nounwind uwtable
 define i32 @readInt() local_unnamed_addr #0 {
@@ -96,7 +198,7 @@ declare i8* @strcpy(i8*, i8* nocapture readonly) local_unnamed_addr #1
 
 ; Function Attrs: nounwind uwtable
 define i8* @__latc_concat_str(i8* nocapture readonly, i8* nocapture readonly) local_unnamed_addr #0 {
-  %3 = tail call i64 @strlen(i8* %0) #10
+  %3 = tail call i64 @strlen(i8* %0) #8
   %4 = tail call i64 @strlen(i8* %1) #10
   %5 = add i64 %3, 1
   %6 = add i64 %5, %4
@@ -121,6 +223,24 @@ define zeroext i1 @__latc_compare_str(i8* nocapture readonly, i8* nocapture read
 ; Function Attrs: nounwind readonly
 declare i32 @strcmp(i8* nocapture, i8* nocapture) local_unnamed_addr #7
 
+; Function Attrs: noreturn nounwind uwtable
+define void @__latc_runtime_error(i8*) local_unnamed_addr #2 {
+  %err = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8
+  %2 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %err, i8* getelementptr inbounds ([19 x i8], [19 x i8]* @.str.5, i64 0, i64 0), i8* %0) #8
+  tail call fastcc void @print_stack_trace()
+  tail call void @exit(i32 1) #9
+  unreachable
+}
+
+; Function Attrs: nounwind uwtable
+define noalias i8* @__latc_alloc(i64) local_unnamed_addr #0 {
+  %2 = tail call noalias i8* @calloc(i64 1, i64 %0) #8
+  ret i8* %2
+}
+
+; Function Attrs: nounwind
+declare noalias i8* @calloc(i64, i64) local_unnamed_addr #1
+
 ; Function Attrs: nounwind
 declare i32 @puts(i8* nocapture readonly) local_unnamed_addr #8
 
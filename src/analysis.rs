@@ -0,0 +1,276 @@
+// Queries about a position in the program, used by the language server.
+use crate::ast::*;
+use crate::frontend::builtin_functions;
+use crate::scoped_map::ScopedMap;
+
+type VarEnv = ScopedMap<Ident, (Span, Type)>;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum CompletionKind {
+    Variable,
+    Function,
+    Class,
+}
+
+#[derive(Debug,Clone)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+// The innermost thing found under the cursor.
+enum Target<'a> {
+    Exp(&'a ExpNode),
+    Field(&'a FieldNode),
+    Var(Span, Ident, Option<(Span, Type)>),
+    Call(Span, Ident),
+    Class(Span, Ident),
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.0 <= offset && offset < span.1
+}
+
+// Span of an identifier placed at the beginning of a node.
+fn ident_span(start: usize, ident: &Ident) -> Span {
+    Span(start, start + ident.len())
+}
+
+fn describe_fn(ident: &Ident, (ret_type, param_types): &FnSignature) -> String {
+    let params: Vec<String> = param_types.iter().map(|t| t.to_string()).collect();
+    format!("{} {}({})", ret_type, ident, params.join(", "))
+}
+
+struct Walker<'a> {
+    offset: usize,
+    env: VarEnv,
+    target: Option<Target<'a>>,
+    visible: Option<VarEnv>, // variables visible at the offset
+}
+
+impl<'a> Walker<'a> {
+    fn new(offset: usize) -> Self {
+        Walker {offset, env: VarEnv::new(), target: None, visible: None}
+    }
+
+    fn var_target(&self, span: Span, ident: &Ident) -> Target<'a> {
+        Target::Var(span, ident.clone(), self.env.get(ident).cloned())
+    }
+
+    fn walk_type(&mut self, type_spec: &'a TypeSpecifier) {
+        if let Type::Class(ident) = &type_spec.ttype {
+            if contains(type_spec.span, self.offset) {
+                self.target = Some(Target::Class(type_spec.span, ident.clone()));
+            }
+        }
+    }
+
+    fn walk_field(&mut self, field_node: &'a FieldNode) {
+        if !contains(field_node.span, self.offset) {
+            return;
+        }
+        match &field_node.field {
+            Field::Direct(obj, _) => {
+                let obj_span = ident_span(field_node.span.0, obj);
+                if contains(obj_span, self.offset) {
+                    self.target = Some(self.var_target(obj_span, obj));
+                    return;
+                }
+            }
+            Field::Indirect(inner, _) => {
+                self.walk_field(inner);
+                if self.target.is_some() {
+                    return;
+                }
+            }
+        }
+        self.target = Some(Target::Field(field_node));
+    }
+
+    fn walk_exp(&mut self, exp_node: &'a ExpNode) {
+        if !contains(exp_node.span, self.offset) {
+            return;
+        }
+        match &exp_node.exp {
+            Exp::Unary(_, inner) => self.walk_exp(inner),
+            Exp::Binary(lexp, _, rexp) => {
+                self.walk_exp(lexp);
+                self.walk_exp(rexp);
+            },
+            Exp::Call(ident, args) => {
+                let name_span = ident_span(exp_node.span.0, ident);
+                if contains(name_span, self.offset) {
+                    self.target = Some(Target::Call(name_span, ident.clone()));
+                    return;
+                }
+                for arg in args {
+                    self.walk_exp(arg);
+                }
+            },
+            Exp::Obj(MemLoc::Var(ident)) => {
+                self.target = Some(self.var_target(exp_node.span, ident));
+                return;
+            },
+            Exp::Obj(MemLoc::Field(field)) => self.walk_field(field),
+            Exp::New(ident) | Exp::Null(ident) => {
+                self.target = Some(Target::Class(exp_node.span, ident.clone()));
+                return;
+            },
+            Exp::Int(_) | Exp::Bool(_) | Exp::Str(_) => (),
+        }
+        if self.target.is_none() {
+            self.target = Some(Target::Exp(exp_node));
+        }
+    }
+
+    fn walk_decls(&mut self, decls: &'a VarDecl) {
+        self.walk_type(&decls.type_spec);
+        for var in &decls.vars {
+            if var.span.0 > self.offset {
+                return;
+            }
+            let name_span = ident_span(var.span.0, &var.ident);
+            if contains(var.span, self.offset) {
+                if contains(name_span, self.offset) {
+                    let decl = (name_span, decls.type_spec.ttype.clone());
+                    self.target = Some(Target::Var(name_span, var.ident.clone(), Some(decl)));
+                } else if let Some(init) = &var.init {
+                    // variable is not visible inside its own initializer
+                    self.walk_exp(init);
+                }
+                return;
+            }
+            self.env.insert_into_top_scope(var.ident.clone(), (name_span, decls.type_spec.ttype.clone()));
+        }
+    }
+
+    fn walk_memloc(&mut self, stmt_node: &'a StmtNode, memloc: &'a MemLoc) {
+        match memloc {
+            MemLoc::Var(ident) => {
+                let name_span = ident_span(stmt_node.span.0, ident);
+                if contains(name_span, self.offset) {
+                    self.target = Some(self.var_target(name_span, ident));
+                }
+            },
+            MemLoc::Field(field) => self.walk_field(field),
+        }
+    }
+
+    fn walk_stmt(&mut self, stmt_node: &'a StmtNode) {
+        match &stmt_node.stmt {
+            Stmt::BStmt(stmts) => {
+                self.env.push_scope();
+                for stmt in stmts {
+                    if stmt.span.0 > self.offset {
+                        break;
+                    }
+                    self.walk_stmt(stmt);
+                    if stmt.span.1 > self.offset {
+                        break;
+                    }
+                }
+                if self.visible.is_none() && contains(stmt_node.span, self.offset) {
+                    self.visible = Some(self.env.clone());
+                }
+                self.env.pop_scope();
+            },
+            Stmt::Decl(decls) => self.walk_decls(decls),
+            Stmt::Ass(memloc, exp) => {
+                self.walk_memloc(stmt_node, memloc);
+                self.walk_exp(exp);
+            },
+            Stmt::Incr(memloc) | Stmt::Decr(memloc) => self.walk_memloc(stmt_node, memloc),
+            Stmt::Ret(exp) | Stmt::EStmt(exp) => self.walk_exp(exp),
+            Stmt::Cond(cond, tstmt, fstmt) => {
+                self.walk_exp(cond);
+                self.walk_stmt(tstmt);
+                if let Some(fstmt) = fstmt {
+                    self.walk_stmt(fstmt);
+                }
+            },
+            Stmt::While(cond, body) => {
+                self.walk_exp(cond);
+                self.walk_stmt(body);
+            },
+            Stmt::VRet | Stmt::Error => (),
+        }
+    }
+
+    fn walk_program(&mut self, prog: &'a Program) {
+        for class in &prog.classes {
+            for field in &class.fields {
+                self.walk_type(&field.type_spec);
+            }
+        }
+        for fdef in &prog.functions {
+            if !(fdef.span.0 <= self.offset && self.offset <= fdef.span.1) {
+                continue;
+            }
+            self.walk_type(&fdef.type_spec);
+            self.env.push_scope();
+            for decls in &fdef.params {
+                self.walk_decls(decls);
+            }
+            self.walk_stmt(&fdef.body);
+            self.env.pop_scope();
+        }
+    }
+}
+
+fn find_target(prog: &Program, offset: usize) -> Option<Target<'_>> {
+    let mut walker = Walker::new(offset);
+    walker.walk_program(prog);
+    walker.target
+}
+
+fn find_fn_signature(prog: &Program, ident: &Ident) -> Option<FnSignature> {
+    prog.functions.iter().find(|f| &f.ident == ident).map(|f| f.get_signature())
+        .or_else(|| builtin_functions().into_iter().find(|(i, _)| i == ident).map(|(_, s)| s))
+}
+
+// Returns the hovered span and a short description of its type.
+pub fn hover(prog: &Program, offset: usize) -> Option<(Span, String)> {
+    match find_target(prog, offset)? {
+        Target::Exp(exp_node) => exp_node.typeval.as_ref().map(|tv| (exp_node.span, tv.describe())),
+        Target::Field(field_node) => field_node.typeval.as_ref().map(|tv| (field_node.span, tv.describe())),
+        Target::Var(span, ident, decl) => decl.map(|(_, ttype)| (span, format!("{}: {}", ident, ttype))),
+        Target::Call(span, ident) => find_fn_signature(prog, &ident).map(|sig| (span, describe_fn(&ident, &sig))),
+        Target::Class(span, ident) => Some((span, format!("class {}", ident))),
+    }
+}
+
+// Returns the span of the definition of the item at the offset.
+pub fn definition(prog: &Program, offset: usize) -> Option<Span> {
+    match find_target(prog, offset)? {
+        Target::Var(_, _, decl) => decl.map(|(span, _)| span),
+        Target::Call(_, ident) => prog.functions.iter().find(|f| f.ident == ident).map(|f| f.span),
+        Target::Class(_, ident) => prog.classes.iter().find(|c| c.ident == ident).map(|c| c.span),
+        Target::Exp(_) | Target::Field(_) => None,
+    }
+}
+
+// Returns identifiers visible at the offset: variables in scope, functions and classes.
+pub fn completions(prog: &Program, offset: usize) -> Vec<Completion> {
+    let mut walker = Walker::new(offset);
+    walker.walk_program(prog);
+
+    let mut result = Vec::new();
+    if let Some(visible) = &walker.visible {
+        let mut idents: Vec<&Ident> = visible.keys().collect();
+        idents.sort();
+        for ident in idents {
+            let (_, ttype) = visible.get(ident).unwrap();
+            result.push(Completion {label: ident.clone(), kind: CompletionKind::Variable, detail: ttype.to_string()});
+        }
+    }
+    let functions = prog.functions.iter().map(|f| (f.ident.clone(), f.get_signature()));
+    for (ident, signature) in functions.chain(builtin_functions()) {
+        let detail = describe_fn(&ident, &signature);
+        result.push(Completion {label: ident, kind: CompletionKind::Function, detail});
+    }
+    for class in &prog.classes {
+        result.push(Completion {label: class.ident.clone(), kind: CompletionKind::Class, detail: format!("class {}", class.ident)});
+    }
+    result
+}
@@ -4,13 +4,47 @@ use std::convert::TryInto;
 #[derive(Debug)]
 pub struct Program {
     pub span: Span,
+    pub imports: Vec<Import>,
     pub functions:  Vec<FnDef>,
     pub classes: Vec<ClassDef>,
+    pub recovered: bool, // some top level definitions were skipped by parser error recovery or not imported
+    pub externs: Interface, // definitions of separately compiled modules, used but not compiled
+}
+
+// Definitions of a module visible to other modules, see -c. Names are the ones used in the module,
+// symbols are the names in the merged program.
+#[derive(Debug,Default,Clone)]
+pub struct Interface {
+    pub functions: Vec<ExternFn>,
+    pub classes: Vec<ExternClass>, // also private classes used by exported definitions
+}
+
+#[derive(Debug,Clone)]
+pub struct ExternFn {
+    pub name: Ident,
+    pub symbol: Ident,
+    pub signature: FnSignature,
+}
+
+#[derive(Debug,Clone)]
+pub struct ExternClass {
+    pub name: Ident,
+    pub symbol: Ident,
+    pub exported: bool,
+    pub fields: Vec<(Ident, Type)>, // in declaration order, which is the layout of objects
+}
+
+// import "path"; the path is relative to the importing file
+#[derive(Debug)]
+pub struct Import {
+    pub span: Span,
+    pub path: String,
 }
 
 pub enum TopDef {
     Fn(FnDef),
     Class(ClassDef),
+    Error,
 }
 
 #[derive(Debug)]
@@ -20,6 +54,7 @@ pub struct FnDef {
     pub ident: Ident,
     pub params: Vec<VarDecl>,
     pub body: Box<StmtNode>,
+    pub exported: bool, // visible in importing modules
 }
 
 #[derive(Debug)]
@@ -27,6 +62,7 @@ pub struct ClassDef {
     pub span: Span,
     pub ident: Ident,
     pub fields: Vec<VarDecl>,
+    pub exported: bool,
 }
 
 #[derive(Debug)]
@@ -50,7 +86,7 @@ pub struct DeclBody {
     pub init: Option<Box<ExpNode>>
 }
 
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,PartialEq)]
 pub enum ExpTypeVal {
     Int(Option<i32>),
     Bool(Option<bool>),
@@ -103,6 +139,16 @@ impl ExpTypeVal {
             _ => true
         }
     }
+
+    // Type with the value computed by constant folding, if known.
+    pub fn describe(&self) -> String {
+        match self {
+            ExpTypeVal::Int(Some(v)) => format!("{} = {}", self, v),
+            ExpTypeVal::Bool(Some(v)) => format!("{} = {}", self, v),
+            ExpTypeVal::Str(Some(v)) => format!("{} = {:?}", self, v),
+            _ => format!("{}", self),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -148,7 +194,7 @@ pub enum Exp {
 pub struct StmtNode {
     pub span: Span,
     pub stmt: Stmt,
-    pub will_return: Option<bool> // always returns
+    pub will_return: Option<bool> // end of the statement is unreachable (it returns, loops forever or diverges)
 }
 
 #[derive(Debug)]
@@ -163,6 +209,7 @@ pub enum Stmt {
     Cond(Box<ExpNode>, Box<StmtNode>, Option<Box<StmtNode>>),
     While(Box<ExpNode>, Box<StmtNode>),
     EStmt(Box<ExpNode>),
+    Error, // skipped by parser error recovery
 }
 
 // *** *** *** Minors *** *** *** //
@@ -174,6 +221,14 @@ pub type FnSignature = (Type, Vec<Type>);
 #[derive(Debug,Clone,Copy)]
 pub struct Span(pub usize, pub usize);
 
+// Error reported by parser actions, e.g. for literals that do not fit into their type.
+#[derive(Debug,Clone)]
+pub struct LexicalError {
+    pub span: Span,
+    pub message: String,
+    pub comment: String,
+}
+
 #[derive(Debug,Clone,PartialEq)]
 pub enum Type {
     Class(Ident),
@@ -198,6 +253,16 @@ pub enum BinaryOp {
 
 // *** *** *** Impls *** *** *** //
 
+impl LexicalError {
+    pub fn int_out_of_range(l: usize, r: usize, literal: &str) -> LexicalError {
+        LexicalError {
+            span: Span(l, r),
+            message: format!("integer literal {} out of range for int", literal),
+            comment: format!("int values range from {} to {}", i32::min_value(), i32::max_value()),
+        }
+    }
+}
+
 impl TypeSpecifier {
     pub fn new(l: usize, r: usize, ttype: Type) -> TypeSpecifier {
         TypeSpecifier { span: Span(l, r), ttype}
@@ -228,6 +293,15 @@ impl StmtNode {
     }
 }
 
+impl ClassDef {
+    // Fields in declaration order, which is the layout of objects.
+    pub fn get_fields(&self) -> Vec<(Ident, Type)> {
+        self.fields.iter()
+            .flat_map(|decl| decl.vars.iter().map(move |var| (var.ident.clone(), decl.type_spec.ttype.clone())))
+            .collect()
+    }
+}
+
 impl FnDef {
     pub fn get_signature(&self) -> FnSignature {
         (self.type_spec.ttype.clone(), self.params.iter().map(|VarDecl {type_spec: ts, ..}| ts.ttype.clone()).collect())
@@ -265,32 +339,6 @@ impl Display for BinaryOp {
     }
 }
 
-//impl Display for Exp {
-//    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-//        match self {
-//            Exp::Unary(op, e) => write!(f, "{}{}", op, e),
-//            Exp::Binary(l, op, r) => write!(f, "({} {} {})", l, op, r),
-//            Exp::Call(ident, args) => {
-//                let mut args_text = String::new();
-//                for arg in args.iter() {
-//                    args_text.push_str(&(**arg).to_string());
-//                    args_text.push_str(", ");
-//                }
-//                let args_text = &args_text[0..args_text.len()-2];
-//                write!(f, "{}({})", ident, args_text)
-//            }
-//            Exp::Int(v) => write!(f, "{}", v),
-//            Exp::Bool(v) => write!(f, "b{}", v),
-//            Exp::Str(v) => write!(f, "{}", v),
-//            Exp::Var(v) => write!(f, "{}", v),
-//        }
-//    }
-//}
-
-//impl Type {
-//
-//}
-
 impl Display for ExpTypeVal {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         match self {
@@ -316,19 +364,21 @@ impl Display for Type {
     }
 }
 
-//impl Debug for Type {
-//    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-//        self.fmt(f)
-//    }
-//}
-
-//impl Display for ExpNode {
-//    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-//        match self.node {
-//            ExpTypeVal::Int => write!(f, "int"),
-//            ExpTypeVal::Bool => write!(f, "boolean"),
-//            ExpTypeVal::Str => write!(f, "string"),
-//            ExpTypeVal::Void => write!(f, "void"),
-//        }
-//    }
-//}
+// As written in the source, e.g. l.next.elem.
+impl Display for FieldNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match &self.field {
+            Field::Direct(obj, field) => write!(f, "{}.{}", obj, field),
+            Field::Indirect(inner, field) => write!(f, "{}.{}", inner, field),
+        }
+    }
+}
+
+impl Display for MemLoc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            MemLoc::Var(ident) => write!(f, "{}", ident),
+            MemLoc::Field(field_node) => write!(f, "{}", field_node),
+        }
+    }
+}
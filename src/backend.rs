@@ -1,33 +1,115 @@
 use crate::scoped_map::ScopedMap;
 use crate::ast::*;
+use crate::options::{Options, Overflow};
+use crate::opt;
+use crate::debuginfo::DebugInfo;
+use crate::cache::{self, Cache, Key};
+use crate::source_map::SourceMap;
 use inkwell::*;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::module::Module;
+use inkwell::module::{Linkage, Module};
 use inkwell::values::*;
 use inkwell::types::*;
 use either::Either;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use inkwell::memory_buffer::MemoryBuffer;
 use std::path::Path;
 use inkwell::basic_block::BasicBlock;
 use inkwell::support::LLVMString;
 
+type VarId = usize;
+type BlockId = usize;
+type PhiId = usize;
+
 type FEnv<'llvm> = HashMap<Ident, FunctionValue<'llvm>>;
-type VEnv<'llvm> = ScopedMap<Ident, BasicValueEnum<'llvm>>; // value env
-type TEnv<'llvm> = ScopedMap<Ident, Type>; // type env
+type VEnv = ScopedMap<Ident, VarId>; // variables visible in current scope
 type SEnv<'llvm> = HashMap<String, GlobalValue<'llvm>>;
 
-struct Backend<'llvm> {
+// SSA construction follows "Simple and Efficient Construction of Static Single Assignment Form"
+// by Braun et al. Values of variables are tracked per basic block, phis are created lazily
+// when a variable is read in a block that does not define it.
+
+// Definition of a variable, a phi may be found trivial later and replaced by another definition.
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum Def<'llvm> {
+    Value(BasicValueEnum<'llvm>),
+    Phi(PhiId),
+}
+
+struct BlockInfo {
+    block: BasicBlock,
+    preds: Vec<BlockId>,
+    sealed: bool, // all predecessors are known
+    incomplete_phis: Vec<PhiId>, // phis created before the block was sealed, they have no operands yet
+}
+
+struct PhiInfo<'llvm> {
+    phi: PhiValue<'llvm>,
+    var: VarId,
+    block: BlockId,
+    operands: Vec<Def<'llvm>>,
+    users: Vec<PhiId>, // phis which have this phi as an operand
+    replacement: Option<Def<'llvm>>,
+}
+
+// Self tail calls of the current function are compiled as jumps to this loop header,
+// which is sealed after the whole body is compiled.
+struct TailCallLoop {
+    ident: Ident,
+    header: BlockId,
+    params: Vec<VarId>,
+}
+
+// Function inlined at the current position, its returns jump to ret_block.
+struct InlineFrame {
+    ident: Ident,
+    ret_block: BlockId,
+    result: Option<VarId>, // variable holding the returned value, none for void functions
+}
+
+// Objects are pointers to structs allocated by __latc_alloc, fields are laid out in declaration order.
+struct ClassInfo<'llvm> {
+    struct_type: StructType<'llvm>,
+    fields: Vec<(Ident, Type)>,
+}
+
+// builtins which cannot end the program, calls to them do not record the line in the shadow stack
+const NON_FAILING_BUILTINS: &[&str] = &["printInt", "printString"];
+
+// inlined functions may call other inlined functions up to this depth
+const MAX_INLINE_DEPTH: usize = 4;
+
+struct Backend<'llvm, 'prog> {
     llvm: &'llvm Context,
     md: Module<'llvm>,
     bd: Builder<'llvm>,
     fenv: FEnv<'llvm>,
-    venv: VEnv<'llvm>,
-    tenv: TEnv<'llvm>,
+    venv: VEnv,
     senv: SEnv<'llvm>,
+    opts: Options,
+    sm: &'prog SourceMap, // for source locations in runtime errors and debug info
+    classes: HashMap<Ident, ClassInfo<'llvm>>,
+    inline_candidates: HashMap<Ident, &'prog FnDef>, // functions small enough to be inlined
+    debug: Option<DebugInfo<'llvm, 'prog>>, // with -g
 
+    // state of the currently compiled function
+    curr_fn_ident: Ident,
     curr_fn: Option<FunctionValue<'llvm>>,
+    curr_block: BlockId,
+    curr_stmt_offset: usize, // start of the compiled statement, line of failed runtime checks
+    blocks: Vec<BlockInfo>,
+    phis: Vec<PhiInfo<'llvm>>,
+    var_types: Vec<Type>,
+    var_defs: Vec<HashMap<BlockId, Def<'llvm>>>, // current definition of a variable in each block
+    tail_call_loop: Option<TailCallLoop>,
+    inline_stack: Vec<InlineFrame>,
+    // objects known to be non-null (for -felide-null-checks): created by new in this function
+    // or already checked earlier in the block
+    allocated: HashSet<PointerValue<'llvm>>,
+    checked: HashSet<(BlockId, PointerValue<'llvm>)>,
+    // with -g variables of the function (not inlined ones) also live in stack slots seen by the debugger
+    debug_slots: HashMap<VarId, PointerValue<'llvm>>,
 }
 
 trait HasSetName {
@@ -47,24 +129,319 @@ impl<'llvm> HasSetName for BasicValueEnum<'llvm> {
     }
 }
 
-impl<'llvm> Backend<'llvm> {
-    fn new(llvm: &'llvm Context, mod_name: &str) -> Backend<'llvm> {
+trait HasReplaceAllUsesWith<'llvm> {
+    fn replace_all_uses_with(&self, value: BasicValueEnum<'llvm>);
+}
+
+// Instruction which produced the value, if any.
+fn get_instruction<'llvm>(value: AnyValueEnum<'llvm>) -> Option<InstructionValue<'llvm>> {
+    match value {
+        AnyValueEnum::IntValue(v) => v.as_instruction(),
+        AnyValueEnum::PointerValue(v) => v.as_instruction(),
+        AnyValueEnum::PhiValue(v) => Some(v.as_instruction()),
+        AnyValueEnum::InstructionValue(v) => Some(v),
+        _ => None,
+    }
+}
+
+impl<'llvm> HasReplaceAllUsesWith<'llvm> for PhiValue<'llvm> {
+    // inkwell can replace an instruction only with another instruction, while a trivial phi
+    // is often replaced by a constant or a parameter, so operands of users are rewritten one by one
+    fn replace_all_uses_with(&self, value: BasicValueEnum<'llvm>) {
+        let phi_inst = self.as_instruction();
+        while let Some(phi_use) = phi_inst.get_first_use() {
+            let user = get_instruction(phi_use.get_user()).expect("phi used by a non-instruction");
+            for i in 0..user.get_num_operands() {
+                if let Some(Either::Left(operand)) = user.get_operand(i) {
+                    if operand.as_instruction_value() == Some(phi_inst) {
+                        user.set_operand(i, value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn contains_self_tail_call(fn_ident: &Ident, stmt_node: &StmtNode) -> bool {
+    match &stmt_node.stmt {
+        Stmt::Ret(exp_node) => match &exp_node.exp {
+            Exp::Call(ident, _) => ident == fn_ident,
+            _ => false,
+        },
+        Stmt::BStmt(stmts) => stmts.iter().any(|s| contains_self_tail_call(fn_ident, s)),
+        Stmt::Cond(_, tstmt, fstmt) => {
+            contains_self_tail_call(fn_ident, tstmt) || fstmt.as_ref().map_or(false, |s| contains_self_tail_call(fn_ident, s))
+        },
+        Stmt::While(_, body) => contains_self_tail_call(fn_ident, body),
+        _ => false,
+    }
+}
+
+// Size of a statement in ast nodes, used by the inlining heuristic.
+fn stmt_size(stmt_node: &StmtNode) -> usize {
+    1 + match &stmt_node.stmt {
+        Stmt::BStmt(stmts) => stmts.iter().map(|s| stmt_size(s)).sum(),
+        Stmt::Decl(decl) => decl.vars.iter().map(|v| v.init.as_ref().map_or(1, |e| exp_size(e))).sum(),
+        Stmt::Ass(_, exp) | Stmt::Ret(exp) | Stmt::EStmt(exp) => exp_size(exp),
+        Stmt::Cond(cond, tstmt, fstmt) => exp_size(cond) + stmt_size(tstmt) + fstmt.as_ref().map_or(0, |s| stmt_size(s)),
+        Stmt::While(cond, body) => exp_size(cond) + stmt_size(body),
+        Stmt::Incr(_) | Stmt::Decr(_) | Stmt::VRet | Stmt::Error => 0,
+    }
+}
+
+fn exp_size(exp_node: &ExpNode) -> usize {
+    1 + match &exp_node.exp {
+        Exp::Unary(_, exp) => exp_size(exp),
+        Exp::Binary(lexp, _, rexp) => exp_size(lexp) + exp_size(rexp),
+        Exp::Call(_, args) => args.iter().map(|a| exp_size(a)).sum(),
+        _ => 0,
+    }
+}
+
+impl<'llvm, 'prog> Backend<'llvm, 'prog> {
+    fn new(llvm: &'llvm Context, mod_name: &str, sm: &'prog SourceMap, opts: &Options) -> Backend<'llvm, 'prog> {
         let md = llvm.create_module(mod_name);
         let bd = llvm.create_builder();
         let fenv = FEnv::new();
         let venv = VEnv::new();
-        let tenv = TEnv::new();
         let senv = SEnv::new();
-        Backend {llvm, md, bd, fenv, venv, tenv, senv, curr_fn: None}
+        let debug = if opts.debug_info { Some(DebugInfo::new(llvm, &md, sm)) } else { None };
+        Backend {
+            llvm, md, bd, fenv, venv, senv,
+            opts: opts.clone(),
+            sm,
+            classes: HashMap::new(),
+            inline_candidates: HashMap::new(),
+            debug,
+            curr_fn_ident: Ident::new(),
+            curr_fn: None,
+            curr_block: 0,
+            curr_stmt_offset: 0,
+            blocks: Vec::new(),
+            phis: Vec::new(),
+            var_types: Vec::new(),
+            var_defs: Vec::new(),
+            tail_call_loop: None,
+            inline_stack: Vec::new(),
+            allocated: HashSet::new(),
+            checked: HashSet::new(),
+            debug_slots: HashMap::new(),
+        }
+    }
+
+    // *** *** *** Control flow *** *** *** //
+
+    fn new_block(&mut self, name: &str) -> BlockId {
+        let block = self.llvm.append_basic_block(self.curr_fn.unwrap(), name);
+        self.blocks.push(BlockInfo {block, preds: Vec::new(), sealed: false, incomplete_phis: Vec::new()});
+        self.blocks.len() - 1
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.curr_block = block;
+        self.bd.position_at_end(&self.blocks[block].block);
+    }
+
+    fn branch(&mut self, target: BlockId) {
+        self.bd.build_unconditional_branch(&self.blocks[target].block);
+        self.blocks[target].preds.push(self.curr_block);
+    }
+
+    fn cond_branch(&mut self, cond: IntValue<'llvm>, then_block: BlockId, else_block: BlockId) {
+        self.bd.build_conditional_branch(cond, &self.blocks[then_block].block, &self.blocks[else_block].block);
+        self.blocks[then_block].preds.push(self.curr_block);
+        self.blocks[else_block].preds.push(self.curr_block);
+    }
+
+    // Must be called once no more predecessors will be added to the block.
+    fn seal_block(&mut self, block: BlockId) {
+        let incomplete_phis: Vec<PhiId> = self.blocks[block].incomplete_phis.drain(..).collect();
+        for phi in incomplete_phis {
+            self.add_phi_operands(phi);
+        }
+        self.blocks[block].sealed = true;
+    }
+
+    // *** *** *** Variables *** *** *** //
+
+    fn new_var(&mut self, ttype: &Type) -> VarId {
+        self.var_types.push(ttype.clone());
+        self.var_defs.push(HashMap::new());
+        self.var_types.len() - 1
+    }
+
+    fn declare_var(&mut self, ident: &Ident, ttype: &Type, value: BasicValueEnum<'llvm>) {
+        let var = self.new_var(ttype);
+        self.venv.insert_into_top_scope(ident.clone(), var);
+        self.write_var(var, value);
+    }
+
+    fn write_var(&mut self, var: VarId, value: BasicValueEnum<'llvm>) {
+        self.var_defs[var].insert(self.curr_block, Def::Value(value));
+    }
+
+    // Gives the just declared variable a stack slot described to the debugger, no-op without -g.
+    // The slot is allocated in the entry block, so loops do not grow the stack.
+    fn declare_debug_var(&mut self, ident: &Ident, ttype: &Type, offset: usize, arg_no: Option<u32>) {
+        if self.debug.is_none() || !self.inline_stack.is_empty() {
+            return;
+        }
+        let var = *self.venv.get(ident).unwrap();
+        let entry = self.blocks[0].block;
+        let slot_bd = self.llvm.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => slot_bd.position_before(&first),
+            None => slot_bd.position_at_end(&entry),
+        }
+        let slot = slot_bd.build_alloca(self.get_llvm_basic_type(ttype).unwrap(), &(ident.clone() + ".dbg"));
+        self.debug.as_ref().unwrap().declare_variable(slot, ident, ttype, offset, arg_no, entry);
+        self.debug_slots.insert(var, slot);
+        let value = self.read_var(var);
+        self.update_debug_var(var, value);
+    }
+
+    // Called on assignments in the source, not on definitions made by the SSA construction.
+    fn update_debug_var(&mut self, var: VarId, value: BasicValueEnum<'llvm>) {
+        if let Some(slot) = self.debug_slots.get(&var) {
+            self.bd.build_store(*slot, value);
+        }
+    }
+
+    fn set_debug_location(&self, offset: usize) {
+        if let Some(debug) = &self.debug {
+            debug.set_location(&self.bd, offset);
+        }
+    }
+
+    fn read_var(&mut self, var: VarId) -> BasicValueEnum<'llvm> {
+        let def = self.read_var_in_block(var, self.curr_block);
+        self.get_def_value(def)
+    }
+
+    fn read_var_in_block(&mut self, var: VarId, block: BlockId) -> Def<'llvm> {
+        match self.var_defs[var].get(&block) {
+            Some(def) => self.resolve_def(*def),
+            None => self.read_var_recursive(var, block),
+        }
+    }
+
+    fn read_var_recursive(&mut self, var: VarId, block: BlockId) -> Def<'llvm> {
+        let def = if !self.blocks[block].sealed {
+            // not all predecessors are known yet, operands are added when the block gets sealed
+            let phi = self.new_phi(var, block);
+            self.blocks[block].incomplete_phis.push(phi);
+            Def::Phi(phi)
+        }
+        else if self.blocks[block].preds.len() == 1 {
+            let pred = self.blocks[block].preds[0];
+            self.read_var_in_block(var, pred)
+        }
+        else {
+            // write the phi first to break cycles
+            let phi = self.new_phi(var, block);
+            self.var_defs[var].insert(block, Def::Phi(phi));
+            self.add_phi_operands(phi)
+        };
+        self.var_defs[var].insert(block, def);
+        def
+    }
+
+    fn new_phi(&mut self, var: VarId, block: BlockId) -> PhiId {
+        let ttype = self.get_llvm_basic_type(&self.var_types[var]).unwrap();
+        // phis go to the beginning of the block, which may already contain some code
+        match self.blocks[block].block.get_first_instruction() {
+            Some(inst) => self.bd.position_before(&inst),
+            None => self.bd.position_at_end(&self.blocks[block].block),
+        }
+        let phi = self.bd.build_phi(ttype, "");
+        self.bd.position_at_end(&self.blocks[self.curr_block].block);
+        self.phis.push(PhiInfo {phi, var, block, operands: Vec::new(), users: Vec::new(), replacement: None});
+        self.phis.len() - 1
+    }
+
+    fn add_phi_operands(&mut self, phi: PhiId) -> Def<'llvm> {
+        let PhiInfo {var, block, ..} = self.phis[phi];
+        for pred in self.blocks[block].preds.clone() {
+            let def = self.read_var_in_block(var, pred);
+            let value = self.get_def_value(def);
+            self.phis[phi].phi.add_incoming(&[(&value, &self.blocks[pred].block)]);
+            self.phis[phi].operands.push(def);
+            if let Def::Phi(operand) = def {
+                self.phis[operand].users.push(phi);
+            }
+        }
+        self.try_remove_trivial_phi(phi)
+    }
+
+    // A phi is trivial if it merges just one value (besides itself), such phi is replaced by that value.
+    fn try_remove_trivial_phi(&mut self, phi: PhiId) -> Def<'llvm> {
+        let this = Def::Phi(phi);
+        let mut same = None;
+        for operand in self.phis[phi].operands.clone() {
+            let operand = self.resolve_def(operand);
+            if Some(operand) == same || operand == this {
+                continue;
+            }
+            if same.is_some() {
+                return this;
+            }
+            same = Some(operand);
+        }
+        let same = match same {
+            Some(def) => def,
+            // unreachable block, the value is never used
+            None => Def::Value(self.get_llvm_undef_value(&self.var_types[self.phis[phi].var])),
+        };
+
+        let phi_value = self.phis[phi].phi;
+        phi_value.replace_all_uses_with(self.get_def_value(same));
+        phi_value.as_instruction().erase_from_basic_block();
+        self.phis[phi].replacement = Some(same);
+
+        // removal of this phi may have made its users trivial
+        for user in self.phis[phi].users.clone() {
+            if user != phi && self.phis[user].replacement.is_none() {
+                self.try_remove_trivial_phi(user);
+            }
+        }
+        same
+    }
+
+    fn resolve_def(&self, def: Def<'llvm>) -> Def<'llvm> {
+        let mut def = def;
+        while let Def::Phi(phi) = def {
+            match self.phis[phi].replacement {
+                Some(replacement) => def = replacement,
+                None => break,
+            }
+        }
+        def
     }
 
+    fn get_def_value(&self, def: Def<'llvm>) -> BasicValueEnum<'llvm> {
+        match self.resolve_def(def) {
+            Def::Value(value) => value,
+            Def::Phi(phi) => self.phis[phi].phi.as_basic_value(),
+        }
+    }
+
+    // *** *** *** Code generation *** *** *** //
+
     fn get_llvm_basic_type(&self, ttype: &Type) -> Option<BasicTypeEnum<'llvm>> {
         match ttype {
             Type::Int =>  Some(self.llvm.i32_type().as_basic_type_enum()),
             Type::Bool => Some(self.llvm.bool_type().as_basic_type_enum()),
             Type::Str =>  Some(self.llvm.i8_type().ptr_type(AddressSpace::Generic).as_basic_type_enum()),
             Type::Void => None,
-            Type::Struct(_) => unimplemented!()
+            Type::Class(ident) => Some(self.classes[ident].struct_type.ptr_type(AddressSpace::Generic).as_basic_type_enum()),
+        }
+    }
+
+    fn get_llvm_undef_value(&self, ttype: &Type) -> BasicValueEnum<'llvm> {
+        match self.get_llvm_basic_type(ttype).unwrap() {
+            BasicTypeEnum::IntType(t) => t.get_undef().into(),
+            BasicTypeEnum::PointerType(t) => t.get_undef().into(),
+            _ => panic!("backend: unexpected type of variable"),
         }
     }
 
@@ -74,7 +451,7 @@ impl<'llvm> Backend<'llvm> {
             Type::Bool => Some(self.llvm.bool_type().const_zero().into()),
             Type::Str => Some(self.llvm.i8_type().const_array(&[self.get_llvm_default_value(&Type::Int).unwrap().into_int_value()]).into()),
             Type::Void => None,
-            Type::Struct(_) => unimplemented!()
+            Type::Class(ident) => Some(self.classes[ident].struct_type.ptr_type(AddressSpace::Generic).const_null().into()),
         }
     }
 
@@ -94,62 +471,73 @@ impl<'llvm> Backend<'llvm> {
             let result = self.bd.build_call(fnval, &argsvals, "").try_as_basic_value();
             result.left().expect("got void from __latc_*_str builtin")
         }
+        // objects are compared by address
+        else if let (_, ExpTypeVal::Class(_), ExpTypeVal::Class(_)) = (op, ltv, rtv) {
+            let lval = self.compile_exp(lexp).unwrap().into_pointer_value();
+            let rval = self.compile_exp(rexp).unwrap().into_pointer_value();
+            let lint = self.bd.build_ptr_to_int(lval, self.llvm.i64_type(), "");
+            let rint = self.bd.build_ptr_to_int(rval, self.llvm.i64_type(), "");
+            match op {
+                BinaryOp::Eq => self.bd.build_int_compare(IntPredicate::EQ, lint, rint, "obj_eq").into(),
+                BinaryOp::Neq => self.bd.build_int_compare(IntPredicate::NE, lint, rint, "obj_neq").into(),
+                _ => panic!("unexpected operator for object operands")
+            }
+        }
         else {
             // lazy evaluation
             match (op, ltv, rtv) {
                 (BinaryOp::Or, ExpTypeVal::Bool(_), ExpTypeVal::Bool(_)) => {
-                    let curr_fn = self.curr_fn.unwrap();
-
-                    let current_bb = self.bd.get_insert_block().unwrap();
-                    let le_lhs_false = self.llvm.append_basic_block(curr_fn, "lazy_eval_or_lhs_false");
-                    let le_done = self.llvm.append_basic_block(curr_fn, "lazy_eval_or_done");
+                    let le_lhs_false = self.new_block("lazy_eval_or_lhs_false");
+                    let le_done = self.new_block("lazy_eval_or_done");
 
                     // evaluate lhs and branch to "done" or rhs evaluation depending whether lhs was conclusive or not
                     let lhs_val = self.compile_exp(lexp).unwrap().into_int_value();
-                    self.bd.build_conditional_branch(
-                        lhs_val,
-                        &le_done,
-                        &le_lhs_false
-                    );
+                    // lhs may be also evaluated lazily, so remember it's "done" block
+                    let lhs_end_block = self.curr_block;
+                    self.cond_branch(lhs_val, le_done, le_lhs_false);
+                    self.seal_block(le_lhs_false);
 
                     // for the case lhs was non-conclusive, emit rhs evaluation in separate block
-                    self.bd.position_at_end(&le_lhs_false);
+                    self.switch_to(le_lhs_false);
                     let rhs_val = self.compile_exp(rexp).unwrap().into_int_value();
-                    // rhs may be also evaluated lazily, so remember it's "done" block
-                    let rhs_end_block = self.bd.get_insert_block().unwrap();
-                    self.bd.build_unconditional_branch(&le_done);
+                    let rhs_end_block = self.curr_block;
+                    self.branch(le_done);
+                    self.seal_block(le_done);
 
                     // build done
-                    self.bd.position_at_end(&le_done);
+                    self.switch_to(le_done);
                     let phi = self.bd.build_phi(self.llvm.bool_type(), "lazy_eval_or_result");
                     let true_value = self.llvm.bool_type().const_int(1, false);
-                    phi.add_incoming(&[(&true_value, &current_bb), (&rhs_val, &rhs_end_block)]);
+                    phi.add_incoming(&[
+                        (&true_value, &self.blocks[lhs_end_block].block),
+                        (&rhs_val, &self.blocks[rhs_end_block].block)
+                    ]);
 
                     phi.as_basic_value()
                 }
                 (BinaryOp::And, ExpTypeVal::Bool(_), ExpTypeVal::Bool(_)) => {
                     // SEE OR FOR COMMENTS
-                    let curr_fn = self.curr_fn.unwrap();
-                    let current_bb = self.bd.get_insert_block().unwrap();
-                    let le_lhs_true = self.llvm.append_basic_block(curr_fn, "lazy_eval_and_lhs_true");
-                    let le_done = self.llvm.append_basic_block(curr_fn, "lazy_eval_and_done");
+                    let le_lhs_true = self.new_block("lazy_eval_and_lhs_true");
+                    let le_done = self.new_block("lazy_eval_and_done");
 
                     let lhs_val = self.compile_exp(lexp).unwrap().into_int_value();
-                    self.bd.build_conditional_branch(
-                        lhs_val,
-                        &le_lhs_true,
-                        &le_done
-                    );
+                    let lhs_end_block = self.curr_block;
+                    self.cond_branch(lhs_val, le_lhs_true, le_done);
+                    self.seal_block(le_lhs_true);
 
-                    self.bd.position_at_end(&le_lhs_true);
+                    self.switch_to(le_lhs_true);
                     let rhs_val = self.compile_exp(rexp).unwrap().into_int_value();
-                    let rhs_end_block = self.bd.get_insert_block().unwrap();
-                    self.bd.build_unconditional_branch(&le_done);
+                    let rhs_end_block = self.curr_block;
+                    self.branch(le_done);
+                    self.seal_block(le_done);
 
-                    self.bd.position_at_end(&le_done);
+                    self.switch_to(le_done);
                     let phi = self.bd.build_phi(self.llvm.bool_type(), "lazy_eval_and_result");
                     let false_value = self.llvm.bool_type().const_int(0, false);
-                    phi.add_incoming(&[(&false_value, &current_bb), (&rhs_val, &rhs_end_block)]);
+                    phi.add_incoming(&[
+                        (&false_value, &self.blocks[lhs_end_block].block),
+                        (&rhs_val, &self.blocks[rhs_end_block].block)
+                    ]);
 
                     phi.as_basic_value()
                 }
@@ -183,19 +571,19 @@ impl<'llvm> Backend<'llvm> {
                             self.bd.build_int_compare(IntPredicate::SLE, lval, rval, "lte").into()
                         }
                         (BinaryOp::Add, ExpTypeVal::Int(_), ExpTypeVal::Int(_)) => {
-                            self.bd.build_int_add(lval, rval, "add").into()
+                            self.build_int_arith(op, lval, rval, "add").into()
                         }
                         (BinaryOp::Sub, ExpTypeVal::Int(_), ExpTypeVal::Int(_)) => {
-                            self.bd.build_int_sub(lval, rval, "sub").into()
+                            self.build_int_arith(op, lval, rval, "sub").into()
                         }
                         (BinaryOp::Mul, ExpTypeVal::Int(_), ExpTypeVal::Int(_)) => {
-                            self.bd.build_int_mul(lval, rval, "mul").into()
+                            self.build_int_arith(op, lval, rval, "mul").into()
                         }
                         (BinaryOp::Mod, ExpTypeVal::Int(_), ExpTypeVal::Int(_)) => {
-                            self.bd.build_int_signed_rem(lval, rval, "mod").into()
+                            self.build_int_division(op, lval, rval, "mod").into()
                         }
                         (BinaryOp::Div, ExpTypeVal::Int(_), ExpTypeVal::Int(_)) => {
-                            self.bd.build_int_signed_div(lval, rval, "div").into()
+                            self.build_int_division(op, lval, rval, "div").into()
                         }
                         _ => panic!("unexpected binary expression")
                     }
@@ -204,139 +592,292 @@ impl<'llvm> Backend<'llvm> {
         }
     }
 
+    fn compile_str_literal(&mut self, str_exp: &str) -> PointerValue<'llvm> {
+        let global_ptr = match self.senv.get(str_exp) {
+            None => {
+                let str_val = self.llvm.const_string(str_exp.as_bytes(), true);
+                let global = self.md.add_global(str_val.get_type(), None, "str_lit");
+                global.set_initializer(&str_val);
+                global.set_linkage(Linkage::Private); // literals of linked modules must not clash
+                self.senv.insert(str_exp.to_owned(), global);
+                self.senv.get(str_exp).unwrap()
+            },
+            Some(g) => g,
+        }.as_pointer_value();
+
+        let zero = self.get_llvm_default_value(&Type::Int).unwrap().into_int_value();
+        unsafe {
+            self.bd.build_gep(global_ptr, &[zero, zero], "")
+        }
+    }
+
+    // Shadow call stack for stack traces printed by the runtime on fatal errors (see --no-stack-trace):
+    // functions push their name on entry and pop it before returning, calls record their line.
+    fn build_push_frame(&mut self, ident: &Ident) {
+        if self.opts.stack_trace {
+            let name = self.compile_str_literal(ident);
+            let fnval = *self.fenv.get("__latc_push_frame").unwrap();
+            self.bd.build_call(fnval, &[name.into()], "");
+        }
+    }
+
+    fn build_pop_frame(&mut self) {
+        if self.opts.stack_trace {
+            let fnval = *self.fenv.get("__latc_pop_frame").unwrap();
+            self.bd.build_call(fnval, &[], "");
+        }
+    }
+
+    fn build_set_line(&mut self, offset: usize) {
+        if self.opts.stack_trace {
+            let line = self.sm.get_location(offset).line;
+            let line = self.llvm.i32_type().const_int(line as u64, false);
+            let fnval = *self.fenv.get("__latc_set_line").unwrap();
+            self.bd.build_call(fnval, &[line.into()], "");
+        }
+    }
+
+    // Continues in a new block if the condition is false, otherwise exits the program with the message.
+    fn build_runtime_check(&mut self, failed: IntValue<'llvm>, message: &str) {
+        let error_block = self.new_block("runtime_error");
+        let ok_block = self.new_block("runtime_ok");
+        self.cond_branch(failed, error_block, ok_block);
+        self.seal_block(error_block);
+        self.seal_block(ok_block);
+
+        self.switch_to(error_block);
+        self.build_set_line(self.curr_stmt_offset);
+        let msg = self.compile_str_literal(message);
+        let fnval = *self.fenv.get("__latc_runtime_error").unwrap();
+        self.bd.build_call(fnval, &[msg.into()], "");
+        self.bd.build_unreachable();
+
+        self.switch_to(ok_block);
+    }
+
+    // +, - and * wrap around or end with a runtime error on overflow, depending on --overflow.
+    fn build_int_arith(&mut self, op: &BinaryOp, lval: IntValue<'llvm>, rval: IntValue<'llvm>, name: &str) -> IntValue<'llvm> {
+        if self.opts.overflow == Overflow::Wrap {
+            return match op {
+                BinaryOp::Add => self.bd.build_int_add(lval, rval, name),
+                BinaryOp::Sub => self.bd.build_int_sub(lval, rval, name),
+                BinaryOp::Mul => self.bd.build_int_mul(lval, rval, name),
+                _ => panic!("unexpected arithmetic operator"),
+            };
+        }
+        let intrinsic = match op {
+            BinaryOp::Add => "llvm.sadd.with.overflow.i32",
+            BinaryOp::Sub => "llvm.ssub.with.overflow.i32",
+            BinaryOp::Mul => "llvm.smul.with.overflow.i32",
+            _ => panic!("unexpected arithmetic operator"),
+        };
+        let fnval = *self.fenv.get(intrinsic).unwrap();
+        let result = self.bd.build_call(fnval, &[lval.into(), rval.into()], "").try_as_basic_value();
+        let result = result.left().expect("got void from overflow intrinsic").into_struct_value();
+        let value = self.bd.build_extract_value(result, 0, name).unwrap().into_int_value();
+        let overflow = self.bd.build_extract_value(result, 1, "overflow").unwrap().into_int_value();
+        self.build_runtime_check(overflow, "integer overflow");
+        value
+    }
+
+    // Division by zero is a runtime error. INT_MIN / -1 overflows: it wraps to INT_MIN (remainder 0)
+    // or is a runtime error, depending on --overflow. In LLVM both are undefined behaviour.
+    fn build_int_division(&mut self, op: &BinaryOp, lval: IntValue<'llvm>, rval: IntValue<'llvm>, name: &str) -> IntValue<'llvm> {
+        let build = |bd: &Builder<'llvm>, lval: IntValue<'llvm>, rval: IntValue<'llvm>| match op {
+            BinaryOp::Div => bd.build_int_signed_div(lval, rval, name),
+            BinaryOp::Mod => bd.build_int_signed_rem(lval, rval, name),
+            _ => panic!("unexpected division operator"),
+        };
+        // constant divisors other than 0 and -1 need no checks
+        match rval.get_sign_extended_constant() {
+            Some(divisor) if divisor != 0 && divisor != -1 => return build(&self.bd, lval, rval),
+            _ => (),
+        }
+
+        let i32_type = self.llvm.i32_type();
+        let zero = i32_type.const_int(0, false);
+        let minus_one = i32_type.const_int(-1i64 as u64, true);
+        let is_zero = self.bd.build_int_compare(IntPredicate::EQ, rval, zero, "div_by_zero");
+        self.build_runtime_check(is_zero, "division by zero");
+
+        let is_minus_one = self.bd.build_int_compare(IntPredicate::EQ, rval, minus_one, "div_by_minus_one");
+        match self.opts.overflow {
+            Overflow::Trap => {
+                let int_min = i32_type.const_int(i32::min_value() as u64, true);
+                let is_int_min = self.bd.build_int_compare(IntPredicate::EQ, lval, int_min, "int_min");
+                let overflow = self.bd.build_and(is_int_min, is_minus_one, "overflow");
+                self.build_runtime_check(overflow, "integer overflow");
+                build(&self.bd, lval, rval)
+            },
+            Overflow::Wrap => {
+                // divide by 1 instead of -1 and negate the result, the negation wraps
+                let one = i32_type.const_int(1, false);
+                let divisor = self.bd.build_select(is_minus_one, one, rval, "divisor").into_int_value();
+                let result = build(&self.bd, lval, divisor);
+                let wrapped = match op {
+                    BinaryOp::Div => self.bd.build_int_neg(lval, "neg"),
+                    _ => zero,
+                };
+                self.bd.build_select(is_minus_one, wrapped, result, name).into_int_value()
+            },
+        }
+    }
+
+    // Exits with "null dereference at file:line:col" if the object is null. With -felide-null-checks
+    // objects created by new and objects already checked in the current block are not checked again.
+    fn build_null_check(&mut self, obj: PointerValue<'llvm>, span: Span) {
+        if self.opts.elide_null_checks && (self.allocated.contains(&obj) || self.checked.contains(&(self.curr_block, obj))) {
+            return;
+        }
+        let is_null = self.bd.build_is_null(obj, "is_null");
+        let message = format!("null dereference at {}", self.sm.format_location(span.0));
+        self.build_runtime_check(is_null, &message);
+        self.checked.insert((self.curr_block, obj));
+    }
+
+    // Pointer to the field, the object is checked for null first.
+    fn compile_field_ptr(&mut self, node: &FieldNode) -> PointerValue<'llvm> {
+        let (obj, class, field) = match &node.field {
+            Field::Direct(ident, field) => {
+                let var = *self.venv.get(ident).unwrap();
+                let class = match &self.var_types[var] {
+                    Type::Class(ident) => ident.clone(),
+                    _ => panic!("backend: field of a non-object"),
+                };
+                (self.read_var(var).into_pointer_value(), class, field)
+            },
+            Field::Indirect(inner, field) => {
+                let class = match inner.typeval.as_ref().unwrap() {
+                    ExpTypeVal::Class(ident) => ident.clone(),
+                    _ => panic!("backend: field of a non-object"),
+                };
+                let inner_ptr = self.compile_field_ptr(inner);
+                (self.bd.build_load(inner_ptr, "").into_pointer_value(), class, field)
+            },
+        };
+        self.build_null_check(obj, node.span);
+        let index = self.classes[&class].fields.iter().position(|(ident, _)| ident == field).unwrap();
+        unsafe {
+            self.bd.build_struct_gep(obj, index as u32, field)
+        }
+    }
+
+    // Allocates a zeroed object, string fields are set to "".
+    fn compile_new(&mut self, class: &Ident) -> PointerValue<'llvm> {
+        let struct_type = self.classes[class].struct_type;
+        let size = struct_type.size_of().unwrap();
+        let fnval = *self.fenv.get("__latc_alloc").unwrap();
+        let raw = self.bd.build_call(fnval, &[size.into()], "").try_as_basic_value().left().unwrap();
+        let obj = self.bd.build_pointer_cast(raw.into_pointer_value(), struct_type.ptr_type(AddressSpace::Generic), class);
+
+        let str_fields: Vec<usize> = self.classes[class].fields.iter().enumerate()
+            .filter(|(_, (_, ttype))| *ttype == Type::Str)
+            .map(|(i, _)| i)
+            .collect();
+        for i in str_fields {
+            let empty = self.compile_str_literal("");
+            let field_ptr = unsafe { self.bd.build_struct_gep(obj, i as u32, "") };
+            self.bd.build_store(field_ptr, empty);
+        }
+        self.allocated.insert(obj);
+        obj
+    }
+
     fn compile_exp(&mut self, node: &ExpNode) -> Option<BasicValueEnum<'llvm>> {
         match &node.exp {
             Exp::Call(ident, args) => {
+                if let Some(fdef) = self.get_inline_candidate(ident) {
+                    return self.compile_inlined_call(fdef, args, node.span.0);
+                }
                 let fnval = *self.fenv.get(ident).unwrap();
                 let argsvals: Vec<BasicValueEnum> = args.iter().map(|x| self.compile_exp(x).unwrap()).collect();
+                // arguments may be on other lines
+                self.set_debug_location(node.span.0);
+                if !NON_FAILING_BUILTINS.contains(&ident.as_str()) {
+                    self.build_set_line(node.span.0);
+                }
                 let result = self.bd.build_call(fnval, &argsvals, "").try_as_basic_value();
                 match result {
                     Either::Left(l) => Some(l),
                     Either::Right(_) => None,
                 }
             },
-            Exp::Var(ident) => Some(*self.venv.get(ident).unwrap()),
+            Exp::Obj(MemLoc::Var(ident)) => {
+                let var = *self.venv.get(ident).unwrap();
+                Some(self.read_var(var))
+            },
             Exp::Int(val) => Some(self.llvm.i32_type().const_int(*val as u64, false).into()),
             Exp::Bool(val) => Some(self.llvm.bool_type().const_int(*val as u64, false).into()),
             Exp::Unary(op, exp) => {
                 let val = self.compile_exp(exp).unwrap();
                 match op {
-                    UnaryOp::Neg => Some(self.bd.build_int_neg(val.into_int_value(), "neg").into()),
+                    UnaryOp::Neg => {
+                        let zero = self.llvm.i32_type().const_int(0, false);
+                        Some(self.build_int_arith(&BinaryOp::Sub, zero, val.into_int_value(), "neg").into())
+                    },
                     UnaryOp::Not => Some(self.bd.build_not(val.into_int_value(), "not").into()),
                 }
             }
             Exp::Binary(lexp, op, rexp) => Some(self.compile_bin_exp(op, lexp, rexp)),
-            Exp::Str(str_exp) => {
-
-                let global_ptr = match self.senv.get(str_exp) {
-                    None => {
-                        let str_val = self.llvm.const_string(str_exp.as_bytes(), true);
-                        let global = self.md.add_global(str_val.get_type(), None, "str_lit");
-                        global.set_initializer(&str_val);
-                        self.senv.insert(str_exp.clone(), global);
-                        self.senv.get(str_exp).unwrap()
-                    },
-                    Some(g) => g,
-                }.as_pointer_value();
-
-                let zero = self.get_llvm_default_value(&Type::Int).unwrap().into_int_value();
-                unsafe {
-                    Some(self.bd.build_gep(global_ptr, &[zero, zero], "").into())
-                }
-            }
-            Exp::Field(_, _) => {unimplemented!()}
-            Exp::New(_) => {unimplemented!()}
+            Exp::Str(str_exp) => Some(self.compile_str_literal(str_exp).into()),
+            Exp::Obj(MemLoc::Field(field)) => {
+                let field_ptr = self.compile_field_ptr(field);
+                Some(self.bd.build_load(field_ptr, ""))
+            },
+            Exp::Null(ident) => self.get_llvm_default_value(&Type::Class(ident.clone())),
+            Exp::New(ident) => Some(self.compile_new(ident).into()),
         }
     }
 
     fn compile_nontrivial_cond_stmt(&mut self, cond: &Box<ExpNode>, tstmt: &Box<StmtNode>, fstmt: &Option<Box<StmtNode>>, node_will_return: bool) {
-        let curr_fn = self.curr_fn.unwrap();
-
-        // create basic block for all statements, they may end up being empty
-        let pred_block = self.bd.get_insert_block().unwrap();
-        let then_block = self.llvm.append_basic_block(curr_fn, "then");
-        let cont_block = self.llvm.append_basic_block(curr_fn, "cont");
-        let else_block = self.llvm.append_basic_block(curr_fn, "else");
-
         let then_returns_if_entered = node_will_return || tstmt.will_return.unwrap();
         let else_returns_if_entered = match fstmt {
             None => false,
             Some(fstmt) => node_will_return || fstmt.will_return.unwrap(),
         };
-        let pred_venv = self.venv.clone();
 
         let cond_val = self.compile_exp(cond).unwrap().into_int_value();
 
-        self.bd.build_conditional_branch(
-            cond_val,
-            &then_block,
-            if fstmt.is_none() { &cont_block } else { &else_block }
-        );
+        // create basic block for all statements, they may end up being empty
+        let then_block = self.new_block("then");
+        let cont_block = self.new_block("cont");
+        let else_block = if fstmt.is_some() { self.new_block("else") } else { cont_block };
+
+        self.cond_branch(cond_val, then_block, else_block);
+        self.seal_block(then_block);
+        if fstmt.is_some() {
+            self.seal_block(else_block);
+        }
 
         // build true-statement block
-        self.bd.position_at_end(&then_block);
+        self.switch_to(then_block);
         self.compile_stmt(tstmt);
         if !then_returns_if_entered {
-            self.bd.build_unconditional_branch(&cont_block);
+            self.branch(cont_block);
         }
-        let then_last_block = self.bd.get_insert_block().unwrap();
-        // expect to know the same set of variables after compiling true statment, possibly with different values
-        assert!(self.venv.keys().eq(pred_venv.keys()));
-
-        // remember variables after true-statement and after optional false-statement
-        let then_venv = self.venv.clone();
-        let mut else_venv = None; // just a placeholder here
 
         // build false-statement block
         if let Some(fstmt) = fstmt {
-            // roll back variables to cancell effects of compilation of true statement
-            self.venv.clone_from(&pred_venv);
-            self.bd.position_at_end(&else_block);
+            self.switch_to(else_block);
             self.compile_stmt(fstmt);
-
             if !else_returns_if_entered {
-                self.bd.build_unconditional_branch(&cont_block);
+                self.branch(cont_block);
             }
-            // expect to know the same set of variables after compiling true statment, possibly with different values
-            assert!(self.venv.keys().eq(pred_venv.keys()));
-            // remember variables
-            else_venv = Some(self.venv.clone());
         }
-        let else_last_block = self.bd.get_insert_block().unwrap();
 
-        // cont block is necessary
+        // values of variables modified in branches are merged by phis created on demand in cont block
+        self.seal_block(cont_block);
         if !node_will_return {
-            self.bd.position_at_end(&cont_block);
-
-            for var in pred_venv.keys() {
-                let mut entries: Vec<(&dyn BasicValue, &BasicBlock)> = Vec::new();
-
-                // no else means we take values from pred
-                if fstmt.is_none() {
-                    let pred_val = pred_venv.get(var).unwrap();
-                    entries.push((pred_val, &pred_block));
-                }
-
-                if !then_returns_if_entered {
-                    let then_val = then_venv.get(var).unwrap();
-                    entries.push((then_val, &then_last_block));
-
-                }
-
-                if fstmt.is_some() && !else_returns_if_entered {
-                    let else_val = else_venv.as_ref().unwrap().get(var).unwrap();
-                    entries.push((else_val, &else_last_block));
-                }
-
-                let ttype = self.get_llvm_basic_type(self.tenv.get(var).unwrap()).unwrap();
-                let phi = self.bd.build_phi(ttype, var);
-                phi.add_incoming(entries.as_slice());
-                self.venv.replace_topmost(var.clone(), phi.as_basic_value());
-            }
+            self.switch_to(cont_block);
         }
     }
 
     fn compile_stmt(&mut self, node: &StmtNode) {
+        // the return added by the frontend at the end of a void function has an empty span
+        if node.span.1 > 0 {
+            self.curr_stmt_offset = node.span.0;
+            self.set_debug_location(node.span.0);
+        }
         match &node.stmt {
             Stmt::BStmt(stmts) => {
                 self.venv.push_scope();
@@ -352,38 +893,69 @@ impl<'llvm> Backend<'llvm> {
                         None => self.get_llvm_default_value(&decl.type_spec.ttype).unwrap()
                     };
                     init_val.set_name(&body.ident);
-                    self.tenv.insert_into_top_scope(body.ident.clone(), decl.type_spec.ttype.clone());
-                    self.venv.insert_into_top_scope(body.ident.clone(), init_val);
+                    self.declare_var(&body.ident, &decl.type_spec.ttype, init_val);
+                    self.declare_debug_var(&body.ident, &decl.type_spec.ttype, body.span.0, None);
                 }
             }
-            Stmt::Ass(ident, exp) => {
+            Stmt::Ass(MemLoc::Var(ident), exp) => {
                 let val = self.compile_exp(exp).unwrap();
                 val.set_name(ident);
-                self.venv.replace_topmost(ident.clone(), val);
+                let var = *self.venv.get(ident).unwrap();
+                self.write_var(var, val);
+                self.update_debug_var(var, val);
             }
-            Stmt::Incr(ident) => {
-                let var = self.venv.get(ident).unwrap().into_int_value();
+            Stmt::Incr(MemLoc::Var(ident)) => {
+                let var = *self.venv.get(ident).unwrap();
+                let old_val = self.read_var(var).into_int_value();
                 let one = self.llvm.i32_type().const_int(1, false);
-                let val: BasicValueEnum = self.bd.build_int_add(var, one, "").into();
+                let val: BasicValueEnum = self.build_int_arith(&BinaryOp::Add, old_val, one, "").into();
                 val.set_name(ident);
-                self.venv.replace_topmost(ident.clone(), val);
+                self.write_var(var, val);
+                self.update_debug_var(var, val);
             }
-            Stmt::Decr(ident) => {
-                let var = self.venv.get(ident).unwrap().into_int_value();
+            Stmt::Decr(MemLoc::Var(ident)) => {
+                let var = *self.venv.get(ident).unwrap();
+                let old_val = self.read_var(var).into_int_value();
                 let one = self.llvm.i32_type().const_int(1, false);
-                let val: BasicValueEnum = self.bd.build_int_sub(var, one, "").into();
+                let val: BasicValueEnum = self.build_int_arith(&BinaryOp::Sub, old_val, one, "").into();
                 val.set_name(ident);
-                self.venv.replace_topmost(ident.clone(), val);
+                self.write_var(var, val);
+                self.update_debug_var(var, val);
+            }
+            Stmt::Ass(MemLoc::Field(field), exp) => {
+                let val = self.compile_exp(exp).unwrap();
+                let field_ptr = self.compile_field_ptr(field);
+                self.bd.build_store(field_ptr, val);
+            }
+            Stmt::Incr(MemLoc::Field(field)) | Stmt::Decr(MemLoc::Field(field)) => {
+                let op = match &node.stmt {
+                    Stmt::Incr(_) => BinaryOp::Add,
+                    _ => BinaryOp::Sub,
+                };
+                let field_ptr = self.compile_field_ptr(field);
+                let old_val = self.bd.build_load(field_ptr, "").into_int_value();
+                let one = self.llvm.i32_type().const_int(1, false);
+                let val = self.build_int_arith(&op, old_val, one, "");
+                self.bd.build_store(field_ptr, val);
             }
             Stmt::EStmt(exp_node) => {
                 self.compile_exp(exp_node);
+                // call of a diverging function, like error()
+                if node.will_return.unwrap() {
+                    self.bd.build_unreachable();
+                }
             },
             Stmt::Ret(node) => {
-                let exp = self.compile_exp(node).unwrap();
-                self.bd.build_return(Some(&exp));
+                match &node.exp {
+                    Exp::Call(ident, args) if self.is_self_call(ident) => self.compile_self_tail_call(args),
+                    _ => {
+                        let exp = self.compile_exp(node).unwrap();
+                        self.compile_return(Some(exp));
+                    }
+                }
             }
             Stmt::VRet => {
-                self.bd.build_return(None);
+                self.compile_return(None);
             }
             Stmt::Cond(cond, tstmt, fstmt) => {
                 match cond.typeval.as_ref().unwrap() {
@@ -401,101 +973,181 @@ impl<'llvm> Backend<'llvm> {
 
             }
             Stmt::While(cond, body) => {
-                let fnval = self.curr_fn.unwrap();
-                let pred_block = self.bd.get_insert_block().unwrap();
-                let pred_venv = self.venv.clone();
-
                 let while_returns = node.will_return.unwrap();
                 let body_returns = body.will_return.unwrap();
-                let cond_block = self.llvm.append_basic_block(fnval, "loop_cond");
-                let body_block = self.llvm.append_basic_block(fnval, "loop_body");
-                let cont_block = self.llvm.append_basic_block(fnval, "loop_cont");
-                self.bd.build_unconditional_branch(&cond_block);
-
-                let mut phi_venv = HashMap::<Ident, PhiValue>::new();
-
-                // build condition (preds = pred, body)
-                {
-                    self.bd.position_at_end(&cond_block);
-
-                    if !while_returns {
-                        // build phi placeholders
-                        for var in self.tenv.keys() {
-                            let ttype = self.get_llvm_basic_type(self.tenv.get(var).unwrap()).unwrap();
-                            let phi = self.bd.build_phi(ttype, var);
-
-                            // does it work?
-                            phi_venv.insert(var.clone(), phi.clone());
-                            self.venv.replace_topmost(var.clone(), phi.as_basic_value());
-                        }
-                    }
-                    // if frontend is sure that this loop returns, jump unconditionally
-                    if while_returns {
-                        self.bd.build_unconditional_branch(&body_block);
-                    }
-                    else {
-                        let cond_val = self.compile_exp(cond).unwrap().into_int_value();
-                        self.bd.build_conditional_branch(cond_val, &body_block, &cont_block);
-                    }
+                let cond_block = self.new_block("loop_cond");
+                let body_block = self.new_block("loop_body");
+                let cont_block = self.new_block("loop_cont");
+                self.branch(cond_block);
+
+                // build condition (preds = pred, body), the block stays unsealed until the body is built,
+                // so variables read there get incomplete phis, which are removed if the loop doesn't modify them
+                self.switch_to(cond_block);
+                // if frontend is sure that this loop returns, jump unconditionally
+                if while_returns {
+                    self.branch(body_block);
                 }
+                else {
+                    let cond_val = self.compile_exp(cond).unwrap().into_int_value();
+                    self.cond_branch(cond_val, body_block, cont_block);
+                }
+                self.seal_block(body_block);
 
                 // build body
-                {
-                    self.bd.position_at_end(&body_block);
-                    self.compile_stmt(body);
-                    if !body_returns {
-                        self.bd.build_unconditional_branch(&cond_block);
-                    }
+                self.switch_to(body_block);
+                self.compile_stmt(body);
+                if !body_returns {
+                    self.branch(cond_block);
                 }
-                let body_last_block = self.bd.get_insert_block().unwrap();
+                self.seal_block(cond_block);
 
+                self.seal_block(cont_block);
                 if !while_returns {
-                    // build actual phi values in cond block
-                    for var in phi_venv.keys() {
-                        let phi = phi_venv.get(var).unwrap();
-                        let pred_val = pred_venv.get(var).unwrap();
-                        let body_val = self.venv.get(var).unwrap();
-                        phi.add_incoming(&[(pred_val, &pred_block), (body_val, &body_last_block)]);
-                        self.venv.replace_topmost(var.clone(), phi.as_basic_value());
-                    }
-                    self.bd.position_at_end(&cont_block);
+                    self.switch_to(cont_block);
                 }
             }
+            Stmt::Error => panic!("backend: syntax error in ast"),
+        }
+    }
+
+    // Returns from the current function, or jumps after the call if the function is inlined.
+    fn compile_return(&mut self, value: Option<BasicValueEnum<'llvm>>) {
+        match self.inline_stack.last() {
+            None => {
+                self.build_pop_frame();
+                self.bd.build_return(value.as_ref().map(|v| v as &dyn BasicValue));
+            },
+            Some(frame) => {
+                let (ret_block, result) = (frame.ret_block, frame.result);
+                if let (Some(var), Some(value)) = (result, value) {
+                    self.write_var(var, value);
+                }
+                self.branch(ret_block);
+            },
+        }
+    }
+
+    // Inlining guards against recursion: the compiled function and functions being inlined are skipped.
+    fn get_inline_candidate(&self, ident: &Ident) -> Option<&'prog FnDef> {
+        let fdef = *self.inline_candidates.get(ident)?;
+        let recursive = *ident == self.curr_fn_ident || self.inline_stack.iter().any(|frame| frame.ident == *ident);
+        if recursive || self.inline_stack.len() >= MAX_INLINE_DEPTH {
+            None
+        } else {
+            Some(fdef)
+        }
+    }
+
+    // Compiles the body of the called function in place of the call.
+    fn compile_inlined_call(&mut self, fdef: &FnDef, args: &[Box<ExpNode>], call_offset: usize) -> Option<BasicValueEnum<'llvm>> {
+        let values: Vec<BasicValueEnum<'llvm>> = args.iter().map(|arg| self.compile_exp(arg).unwrap()).collect();
+        let ret_block = self.new_block("inline_ret");
+        let result = match &fdef.type_spec.ttype {
+            Type::Void => None,
+            ttype => Some(self.new_var(ttype)),
+        };
+
+        // the callee sees only its parameters, returns inside it are not tail calls of the caller
+        let caller_venv = std::mem::replace(&mut self.venv, VEnv::new());
+        let caller_tail_call_loop = self.tail_call_loop.take();
+        for (param, value) in fdef.params.iter().zip(values) {
+            let name = &param.vars.first().unwrap().ident;
+            self.declare_var(name, &param.type_spec.ttype, value);
         }
+        let caller_stmt_offset = self.curr_stmt_offset;
+        // the frame of the caller shows the line of the call, as for calls which are not inlined
+        self.build_set_line(call_offset);
+        self.build_push_frame(&fdef.ident);
+        if let Some(debug) = &mut self.debug {
+            debug.enter_inlined(&fdef.ident, call_offset);
+        }
+        self.inline_stack.push(InlineFrame {ident: fdef.ident.clone(), ret_block, result});
+        self.compile_stmt(&fdef.body);
+        self.inline_stack.pop();
+        if let Some(debug) = &mut self.debug {
+            debug.leave_inlined();
+        }
+        self.venv = caller_venv;
+        self.tail_call_loop = caller_tail_call_loop;
+        self.curr_stmt_offset = caller_stmt_offset;
+
+        self.seal_block(ret_block);
+        self.switch_to(ret_block);
+        self.set_debug_location(call_offset);
+        self.build_pop_frame();
+        result.map(|var| self.read_var(var))
+    }
+
+    fn is_self_call(&self, ident: &Ident) -> bool {
+        self.tail_call_loop.as_ref().map_or(false, |tcl| &tcl.ident == ident)
+    }
+
+    // Assigns arguments to parameters and jumps back to the beginning of the function.
+    fn compile_self_tail_call(&mut self, args: &[Box<ExpNode>]) {
+        // all arguments are evaluated before any parameter changes
+        let values: Vec<BasicValueEnum<'llvm>> = args.iter().map(|arg| self.compile_exp(arg).unwrap()).collect();
+        let tcl = self.tail_call_loop.as_ref().unwrap();
+        let (header, params) = (tcl.header, tcl.params.clone());
+        for (param, value) in params.into_iter().zip(values) {
+            self.write_var(param, value);
+            self.update_debug_var(param, value);
+        }
+        self.branch(header);
     }
 
     fn compile_fndef(&mut self, fndef: &FnDef) {
         let fnval = *self.fenv.get(&fndef.ident).unwrap();
         self.curr_fn = Some(fnval);
-        let entry = self.llvm.append_basic_block(fnval, "entry");
+        self.curr_fn_ident = fndef.ident.clone();
+        self.blocks.clear();
+        self.phis.clear();
+        self.var_types.clear();
+        self.var_defs.clear();
         self.venv = VEnv::new();
-        self.tenv = TEnv::new();
+        self.tail_call_loop = None;
+        self.allocated.clear();
+        self.checked.clear();
+        self.debug_slots.clear();
+
+        let entry = self.new_block("entry");
+        self.seal_block(entry);
+        self.switch_to(entry);
+        if let Some(debug) = &mut self.debug {
+            debug.enter_function(&fndef.ident);
+        }
+        self.set_debug_location(fndef.span.0);
         for (i, param) in fndef.params.iter().enumerate() {
             let name = &param.vars.first().unwrap().ident;
             let val = fnval.get_nth_param(i as u32).unwrap();
-            self.venv.insert_into_top_scope(name.clone(), val);
-            self.tenv.insert_into_top_scope(name.clone(), param.type_spec.ttype.clone());
+            self.declare_var(name, &param.type_spec.ttype, val);
+            self.declare_debug_var(name, &param.type_spec.ttype, param.span.0, Some(i as u32 + 1));
+        }
+        // pushed before the tail call loop, self tail calls reuse the frame
+        self.build_push_frame(&fndef.ident);
+
+        // parameters read in the header get phis, trivial ones if a tail call passes them unchanged
+        if contains_self_tail_call(&fndef.ident, &fndef.body) {
+            let header = self.new_block("tail_call_loop");
+            self.branch(header);
+            self.switch_to(header);
+            // parameters were declared first, so their ids are 0..n
+            let params = (0..fndef.params.len()).collect();
+            self.tail_call_loop = Some(TailCallLoop {ident: fndef.ident.clone(), header, params});
         }
-        self.bd.position_at_end(&entry);
+
         self.compile_stmt(&fndef.body);
+        if let Some(tcl) = &self.tail_call_loop {
+            self.seal_block(tcl.header);
+        }
         self.remove_empty_basic_blocks();
     }
 
+    // Removes blocks of the current function that ended up empty, e.g. cont blocks of returning ifs.
     fn remove_empty_basic_blocks(&mut self) {
-        let mut ff = self.md.get_first_function();
-        while let Some(function) = ff {
-
-            let mut bb = function.get_first_basic_block();
-            while let Some(basic_block) = bb {
-                let next = basic_block.get_next_basic_block();
-                if let None = basic_block.get_first_instruction() {
-                    basic_block.remove_from_function().expect("error while removing unused basic blocks");
-                }
-                bb = next;
-
+        for info in &self.blocks {
+            if let None = info.block.get_first_instruction() {
+                info.block.remove_from_function().expect("error while removing unused basic blocks");
             }
-
-            ff = function.get_next_function();
         }
     }
 
@@ -510,16 +1162,69 @@ impl<'llvm> Backend<'llvm> {
         self.fenv.insert(ident.clone(), fnval);
     }
 
-    fn compile_prog(&mut self, prog: &Program) {
+    // {i32 result, i1 overflow} llvm.s{add,sub,mul}.with.overflow.i32(i32, i32)
+    fn declare_overflow_intrinsics(&mut self) {
+        let i32_type = self.llvm.i32_type();
+        let result_type = self.llvm.struct_type(&[i32_type.into(), self.llvm.bool_type().into()], false);
+        let fn_type = result_type.fn_type(&[i32_type.into(), i32_type.into()], false);
+        for name in &["llvm.sadd.with.overflow.i32", "llvm.ssub.with.overflow.i32", "llvm.smul.with.overflow.i32"] {
+            let fnval = self.md.add_function(name, fn_type, None);
+            self.fenv.insert(name.to_string(), fnval);
+        }
+    }
+
+    // Struct types are declared first, so fields can refer to any class.
+    fn compile_classes(&mut self, classes: Vec<(Ident, Vec<(Ident, Type)>)>) {
+        for (ident, fields) in &classes {
+            let struct_type = self.llvm.opaque_struct_type(ident);
+            self.classes.insert(ident.clone(), ClassInfo {struct_type, fields: fields.clone()});
+        }
+        for (ident, _) in &classes {
+            let field_types: Vec<BasicTypeEnum> = self.classes[ident].fields.iter()
+                .map(|(_, ttype)| self.get_llvm_basic_type(ttype).unwrap())
+                .collect();
+            self.classes[ident].struct_type.set_body(&field_types, false);
+        }
+    }
+
+    fn compile_prog(&mut self, prog: &'prog Program) {
         self.compile_fndecl(&"readInt".to_owned(), &(Type::Int, vec![]));
         self.compile_fndecl(&"readString".to_owned(), &(Type::Str, vec![]));
         self.compile_fndecl(&"printInt".to_owned(), &(Type::Void, vec![Type::Int]));
         self.compile_fndecl(&"printString".to_owned(), &(Type::Void, vec![Type::Str]));
+        self.compile_fndecl(&"error".to_owned(), &(Type::Void, vec![]));
         self.compile_fndecl(&"__latc_concat_str".to_owned(), &(Type::Str, vec![Type::Str, Type::Str]));
         self.compile_fndecl(&"__latc_compare_str".to_owned(), &(Type::Bool, vec![Type::Str, Type::Str]));
+        self.compile_fndecl(&"__latc_runtime_error".to_owned(), &(Type::Void, vec![Type::Str]));
+        let alloc_type = self.llvm.i8_type().ptr_type(AddressSpace::Generic).fn_type(&[self.llvm.i64_type().into()], false);
+        let alloc = self.md.add_function("__latc_alloc", alloc_type, None);
+        self.fenv.insert("__latc_alloc".to_owned(), alloc);
+        if self.opts.overflow == Overflow::Trap {
+            self.declare_overflow_intrinsics();
+        }
+        if self.opts.stack_trace {
+            self.compile_fndecl(&"__latc_push_frame".to_owned(), &(Type::Void, vec![Type::Str]));
+            self.compile_fndecl(&"__latc_pop_frame".to_owned(), &(Type::Void, vec![]));
+            self.compile_fndecl(&"__latc_set_line".to_owned(), &(Type::Void, vec![Type::Int]));
+        }
+
+        // classes and functions of separately compiled modules are only declared
+        let classes = prog.classes.iter().map(|class| (class.ident.clone(), class.get_fields()))
+            .chain(prog.externs.classes.iter().map(|class| (class.symbol.clone(), class.fields.clone())))
+            .collect();
+        self.compile_classes(classes);
+        for extern_fn in &prog.externs.functions {
+            self.compile_fndecl(&extern_fn.symbol, &extern_fn.signature);
+        }
 
         for fndef in &prog.functions {
             self.compile_fndecl(&fndef.ident, &fndef.get_signature());
+            if let Some(debug) = &mut self.debug {
+                debug.declare_function(self.fenv[&fndef.ident], fndef);
+            }
+            if stmt_size(&fndef.body) <= self.opts.inline_limit {
+                self.inline_candidates.insert(fndef.ident.clone(), fndef);
+            }
         }
 
         for fndef in &prog.functions {
@@ -529,29 +1234,65 @@ impl<'llvm> Backend<'llvm> {
     }
 }
 
-pub fn compile(prog: &Program, path: &Path) -> Result<(), LLVMString> {
-    // split path
+pub fn compile(prog: &Program, sm: &SourceMap, path: &Path, opts: &Options) -> Result<(), LLVMString> {
     let mod_name = path.file_stem().unwrap().to_str().unwrap().to_owned();
-    let dir_path = path.parent().unwrap_or(Path::new("."));
+
+    // --dump-ir and --opt-stats print while compiling, they bypass the cache
+    let cache = match &opts.cache_dir {
+        Some(dir) if !opts.dump_ir && !opts.opt_stats => Some((Cache::new(dir), Key::new(prog, sm, path, cache::build_id(), RUNTIME, opts))),
+        _ => None,
+    };
+    if let Some((cache, key)) = &cache {
+        let hit = cache.restore(*key, |ext| opts.output_path(path, ext));
+        if opts.verbose {
+            eprintln!("cache {}: {}", if hit { "hit" } else { "miss" }, path.display());
+        }
+        if hit {
+            return Ok(());
+        }
+    }
 
     // init things
     let llvm = Context::create();
-    let mut backend = Backend::new(&llvm, &mod_name);
+    let mut backend = Backend::new(&llvm, &mod_name, sm, opts);
 
     // load runtime
-    let rt_buffer = MemoryBuffer::create_from_file(Path::new("lib/runtime.ll")).unwrap();
-    let rt_mod = backend.llvm.create_module_from_ir(rt_buffer).unwrap();
+    let rt_mod = load_runtime(backend.llvm);
 
-    // compile & link
+    // compile, optimize & link
     backend.compile_prog(prog);
-    backend.md.link_in_module(rt_mod).unwrap();
+    if let Some(debug) = &backend.debug {
+        debug.finalize();
+    }
+    if opts.dump_ir {
+        println!("; IR before optimizations\n{}", backend.md.print_to_string());
+    }
+    let stats = if opts.optimize { opt::optimize_module(backend.llvm, &backend.md) } else { opt::Stats::default() };
+    if opts.opt_stats {
+        eprintln!("{}", stats);
+    }
+    if opts.dump_ir {
+        println!("; IR after optimizations\n{}", backend.md.print_to_string());
+    }
+    // the runtime is linked into the program by latte link
+    if !opts.compile_only {
+        backend.md.link_in_module(rt_mod).unwrap();
+    }
 
     // handle result
 //    println!("IGNORING ANY LLVM ERRORS");
     match backend.md.verify() {
         Ok(_) => {
-            backend.md.print_to_file(dir_path.join(Path::new(&(mod_name.clone() + ".ll"))))?;
-            backend.md.write_bitcode_to_path(dir_path.join(Path::new(&(mod_name.clone() + ".bc"))).as_path());
+            backend.md.print_to_file(opts.output_path(path, "ll"))?;
+            backend.md.write_bitcode_to_path(&opts.output_path(path, "bc"));
+            // the outputs are written already, a failed store only costs a later compilation
+            if let Some((cache, key)) = &cache {
+                if let Err(e) = cache.store(*key, |ext| opts.output_path(path, ext)) {
+                    if opts.verbose {
+                        eprintln!("cache: cannot store {}: {}", path.display(), e);
+                    }
+                }
+            }
             Ok(())
         },
         Err(e) => Err(e)
@@ -559,3 +1300,31 @@ pub fn compile(prog: &Program, path: &Path) -> Result<(), LLVMString> {
 
 
 }
+
+// Built into the compiler, so it works outside of the repository too, e.g. in latte build.
+const RUNTIME: &str = include_str!("../lib/runtime.ll");
+
+fn load_runtime(llvm: &Context) -> Module<'_> {
+    let rt_buffer = MemoryBuffer::create_from_memory_range_copy(RUNTIME.as_bytes(), "runtime");
+    llvm.create_module_from_ir(rt_buffer).unwrap()
+}
+
+// Links modules compiled with -c and the runtime into a program, written to output (.bc) and next
+// to it as .ll.
+pub fn link(inputs: &[&Path], output: &Path) -> Result<(), String> {
+    let llvm = Context::create();
+    let md = llvm.create_module("main");
+    for input in inputs {
+        let input_md = Module::parse_bitcode_from_path(input, &llvm)
+            .map_err(|e| format!("cannot read {}: {}", input.display(), e))?;
+        md.link_in_module(input_md).map_err(|e| format!("cannot link {}: {}", input.display(), e))?;
+    }
+    md.link_in_module(load_runtime(&llvm)).unwrap();
+    if md.get_function("main").is_none() {
+        return Err("no main function in linked modules".to_owned());
+    }
+    md.verify().map_err(|e| e.to_string())?;
+    md.print_to_file(output.with_extension("ll")).map_err(|e| e.to_string())?;
+    md.write_bitcode_to_path(output);
+    Ok(())
+}
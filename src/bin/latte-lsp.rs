@@ -0,0 +1,299 @@
+// Language server for Latte, speaks LSP (JSON-RPC with Content-Length framing) over stdio.
+use latte::{analysis, ast, check_program, diag};
+use latte::options::Options;
+use latte::source_map::SourceMap;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+struct Document {
+    text: String,
+    ast: Option<ast::Program>, // merged with imported files
+    sm: SourceMap, // the document is the first file
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let content_length = content_length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    serde_json::from_slice(&content).map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()
+}
+
+// LSP positions count lines from 0 and columns in UTF-16 code units.
+fn offset_to_position(text: &str, offset: usize) -> Value {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[..line_start].matches('\n').count();
+    let character: usize = text[line_start..].char_indices()
+        .take_while(|(i, _)| line_start + i < offset)
+        .map(|(_, ch)| ch.len_utf16())
+        .sum();
+    json!({"line": line, "character": character})
+}
+
+fn position_to_offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    let line_start = match line {
+        0 => 0,
+        _ => match text.match_indices('\n').nth(line - 1) {
+            Some((i, _)) => i + 1,
+            None => return text.len(),
+        }
+    };
+    let mut units = 0;
+    for (i, ch) in text[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return line_start + i;
+        }
+        units += ch.len_utf16();
+    }
+    text.len()
+}
+
+fn span_to_range(text: &str, span: ast::Span) -> Value {
+    json!({"start": offset_to_position(text, span.0), "end": offset_to_position(text, span.1)})
+}
+
+// Path of a file:// URI, with %XX escapes decoded. Other URIs are used as names as they are.
+fn uri_to_path(uri: &str) -> String {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => path.as_bytes(),
+        None => return uri.to_owned(),
+    };
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let escaped = path.get(i + 1..i + 3)
+            .filter(|_| path[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 3;
+            },
+            None => {
+                bytes.push(path[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// The file:// URI of an absolute path, bytes other than unreserved characters and / are escaped.
+fn path_to_uri(path: &std::path::Path) -> String {
+    let mut uri = "file://".to_owned();
+    for &byte in path.to_string_lossy().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri += &format!("%{:02X}", byte);
+        }
+    }
+    uri
+}
+
+fn diagnostic_to_json(text: &str, diagnostic: &diag::Diagnostic) -> Value {
+    let (range, message) = match &diagnostic.details {
+        Some((span, comment)) => (span_to_range(text, *span), format!("{}\n{}", diagnostic.message, comment)),
+        None => (span_to_range(text, ast::Span(0, 0)), diagnostic.message.clone()),
+    };
+    let severity = match diagnostic.severity {
+        diag::Severity::Error => 1,
+        diag::Severity::Warning => 2,
+    };
+    json!({
+        "range": range,
+        "severity": severity,
+        "code": diagnostic.code,
+        "source": "latte",
+        "message": message,
+    })
+}
+
+impl Server {
+    fn new() -> Self {
+        Server {documents: HashMap::new(), shutdown: false}
+    }
+
+    // Imports are read from disk, relative to the document. Diagnostics in imported files are
+    // not published for this document.
+    fn update_document(&mut self, uri: &str, text: String, output: &mut impl Write) -> io::Result<()> {
+        let mut sm = SourceMap::new();
+        let file = sm.add_file(&uri_to_path(uri), text.clone());
+        let (ast, diags) = check_program(&mut sm, file, &Options::default());
+        let diagnostics: Vec<Value> = diags.iter()
+            .filter(|d| d.details.as_ref().map_or(true, |(span, _)| sm.lookup_file(span.0) == file))
+            .map(|d| diagnostic_to_json(&text, d))
+            .collect();
+        self.documents.insert(uri.to_owned(), Document {text, ast, sm});
+        publish_diagnostics(output, uri, diagnostics)
+    }
+
+    // Returns the document and byte offset pointed by textDocument/position params.
+    fn locate(&self, params: &Value) -> Option<(&Document, usize)> {
+        let document = self.documents.get(params["textDocument"]["uri"].as_str()?)?;
+        let offset = position_to_offset(&document.text, &params["position"]);
+        Some((document, offset))
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let hover = self.locate(params).and_then(|(document, offset)| {
+            let (span, text) = analysis::hover(document.ast.as_ref()?, offset)?;
+            Some(json!({
+                "contents": {"kind": "plaintext", "value": text},
+                "range": span_to_range(&document.text, span),
+            }))
+        });
+        hover.unwrap_or(Value::Null)
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let location = self.locate(params).and_then(|(document, offset)| {
+            let span = analysis::definition(document.ast.as_ref()?, offset)?;
+            let file = document.sm.lookup_file(span.0);
+            if file == 0 {
+                return Some(json!({
+                    "uri": params["textDocument"]["uri"],
+                    "range": span_to_range(&document.text, span),
+                }));
+            }
+            // defined in an imported file
+            let start = document.sm.get_start(file);
+            let path = std::fs::canonicalize(document.sm.get_name(file)).ok()?;
+            Some(json!({
+                "uri": path_to_uri(&path),
+                "range": span_to_range(document.sm.get_content(file), ast::Span(span.0 - start, span.1 - start)),
+            }))
+        });
+        location.unwrap_or(Value::Null)
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let items = self.locate(params).and_then(|(document, offset)| {
+            let completions = analysis::completions(document.ast.as_ref()?, offset);
+            // definitions of imported files have names prefixed with the module, which cannot be typed
+            Some(completions.into_iter().filter(|c| !c.label.contains('.')).map(|c| {
+                // CompletionItemKind values from the LSP specification
+                let kind = match c.kind {
+                    analysis::CompletionKind::Variable => 6,
+                    analysis::CompletionKind::Function => 3,
+                    analysis::CompletionKind::Class => 7,
+                };
+                json!({"label": c.label, "kind": kind, "detail": c.detail})
+            }).collect::<Vec<Value>>())
+        });
+        json!(items.unwrap_or_default())
+    }
+
+    // Handles a request, returns its result or an error (code, message).
+    fn handle_request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1, // full document is sent on every change
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": {"name": "latte-lsp", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/completion" => Ok(self.completion(params)),
+            _ => Err((-32601, format!("method not found: {}", method))),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value, output: &mut impl Write) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.update_document(uri, text.to_owned(), output)
+            },
+            "textDocument/didChange" => {
+                // full sync, the last change holds the whole text
+                match params["contentChanges"].as_array().and_then(|c| c.last()) {
+                    Some(change) => {
+                        let text = change["text"].as_str().unwrap_or_default();
+                        self.update_document(uri, text.to_owned(), output)
+                    },
+                    None => Ok(()),
+                }
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                publish_diagnostics(output, uri, vec![])
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+fn publish_diagnostics(output: &mut impl Write, uri: &str, diagnostics: Vec<Value>) -> io::Result<()> {
+    write_message(output, &json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    }))
+}
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut input = stdin.lock();
+    let mut output = stdout.lock();
+    let mut server = Server::new();
+
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        match message.get("id") {
+            Some(id) => {
+                let response = match server.handle_request(method, params) {
+                    Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                    Err((code, msg)) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": msg}}),
+                };
+                write_message(&mut output, &response)?;
+            },
+            None if method == "exit" => {
+                std::process::exit(if server.shutdown { 0 } else { 1 });
+            },
+            None => server.handle_notification(method, params, &mut output)?,
+        }
+    }
+    Ok(())
+}
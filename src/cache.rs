@@ -0,0 +1,125 @@
+// On-disk cache of compiled modules, enabled with --cache. Outputs (.ll and .bc) are stored under a
+// 64-bit FNV-1a hash of everything they depend on: compiler build, runtime, code generation options,
+// names and texts of all source files and interfaces of separately compiled imports. On a hit the
+// backend copies the stored outputs instead of compiling.
+use crate::ast::Program;
+use crate::interface;
+use crate::options::Options;
+use crate::source_map::SourceMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+const EXTENSIONS: [&str; 2] = ["ll", "bc"];
+
+// For the summary printed with --verbose, there is one compilation per file in latte build.
+static HITS: AtomicUsize = AtomicUsize::new(0);
+static MISSES: AtomicUsize = AtomicUsize::new(0);
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // The length goes first, so consecutive parts can't be confused, e.g. "ab" "c" and "a" "bc".
+    fn part(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+// Hash of the compiler executable, so entries of other builds of the same version are not used.
+// Computed once, 0 means not yet.
+static BUILD_ID: AtomicU64 = AtomicU64::new(0);
+
+pub fn build_id() -> u64 {
+    let id = BUILD_ID.load(Ordering::Relaxed);
+    if id != 0 {
+        return id;
+    }
+    let mut hash = Fnv::new();
+    match std::env::current_exe().and_then(fs::read) {
+        Ok(exe) => hash.part(&exe),
+        Err(_) => hash.part(env!("CARGO_PKG_VERSION").as_bytes()),
+    }
+    BUILD_ID.store(hash.0, Ordering::Relaxed);
+    hash.0
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Key(u64);
+
+impl Key {
+    // build_id identifies the compiler, see build_id().
+    pub fn new(prog: &Program, sm: &SourceMap, path: &Path, build_id: u64, runtime: &str, opts: &Options) -> Key {
+        let mut hash = Fnv::new();
+        hash.part(&build_id.to_le_bytes());
+        hash.part(runtime.as_bytes());
+        hash.part(opts.codegen_options().as_bytes());
+        hash.part(path.to_string_lossy().as_bytes());
+        for file in 0..sm.len() {
+            hash.part(sm.get_name(file).as_bytes());
+            hash.part(sm.get_content(file).as_bytes());
+        }
+        hash.part(interface::to_json(&prog.externs).to_string().as_bytes());
+        Key(hash.0)
+    }
+}
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: &str) -> Cache {
+        Cache {dir: PathBuf::from(dir)}
+    }
+
+    fn entry(&self, key: Key, extension: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.{}", key.0, extension))
+    }
+
+    // Copies stored outputs to the paths given for their extensions, false on a miss.
+    pub fn restore(&self, key: Key, output_path: impl Fn(&str) -> PathBuf) -> bool {
+        let hit = EXTENSIONS.iter().all(|ext| self.entry(key, ext).is_file())
+            && EXTENSIONS.iter().all(|ext| fs::copy(self.entry(key, ext), output_path(ext)).is_ok());
+        let counter = if hit { &HITS } else { &MISSES };
+        counter.fetch_add(1, Ordering::Relaxed);
+        hit
+    }
+
+    // Entries are renamed into place, so concurrent compilations never read half written files.
+    pub fn store(&self, key: Key, output_path: impl Fn(&str) -> PathBuf) -> Result<(), io::Error> {
+        fs::create_dir_all(&self.dir)?;
+        for ext in &EXTENSIONS {
+            let tmp = self.dir.join(format!("{:016x}.{}.{}.tmp", key.0, ext, std::process::id()));
+            fs::copy(output_path(ext), &tmp)?;
+            fs::rename(&tmp, self.entry(key, ext))?;
+        }
+        Ok(())
+    }
+
+    // "cache: 2 hits, 1 miss, 6 files (1234 bytes) in .latte-cache"
+    pub fn summary(&self) -> String {
+        let plural = |n: usize, word: &str, suffix: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { suffix });
+        let (files, bytes) = fs::read_dir(&self.dir).map(|entries| {
+            entries.filter_map(Result::ok)
+                .filter_map(|entry| entry.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .fold((0, 0), |(files, bytes), metadata| (files + 1, bytes + metadata.len()))
+        }).unwrap_or((0, 0));
+        format!("cache: {}, {}, {} ({} bytes) in {}",
+                plural(HITS.load(Ordering::Relaxed), "hit", "s"), plural(MISSES.load(Ordering::Relaxed), "miss", "es"),
+                plural(files, "file", "s"), bytes, self.dir.display())
+    }
+}
@@ -0,0 +1,133 @@
+// DWARF debug information emitted with -g: a compile unit for the source file, a subprogram for every
+// function, line locations of statements and calls, and descriptions of local variables.
+use crate::ast::*;
+use crate::source_map::SourceMap;
+use inkwell::AddressSpace;
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::Builder;
+use inkwell::context::Context;
+use inkwell::debug_info::*;
+use inkwell::module::{FlagBehavior, Module};
+use inkwell::values::{FunctionValue, PointerValue};
+use std::collections::HashMap;
+use std::path::Path;
+
+// DWARF base type encodings
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_SIGNED_CHAR: u32 = 0x06;
+
+pub struct DebugInfo<'llvm, 'prog> {
+    llvm: &'llvm Context,
+    sm: &'prog SourceMap,
+    dibuilder: DebugInfoBuilder<'llvm>,
+    compile_unit: DICompileUnit<'llvm>,
+    di_files: Vec<DIFile<'llvm>>, // for every file of the source map
+    subprograms: HashMap<Ident, DISubprogram<'llvm>>,
+    // innermost last: the compiled function, then inlined functions with locations of their calls
+    scopes: Vec<(DISubprogram<'llvm>, Option<DILocation<'llvm>>)>,
+}
+
+impl<'llvm, 'prog> DebugInfo<'llvm, 'prog> {
+    // The compile unit is the first file of the source map.
+    pub fn new(llvm: &'llvm Context, md: &Module<'llvm>, sm: &'prog SourceMap) -> DebugInfo<'llvm, 'prog> {
+        let (filename, directory) = split_path(sm.get_name(0));
+        md.add_basic_value_flag("Debug Info Version", FlagBehavior::Warning, llvm.i32_type().const_int(3, false));
+        md.add_basic_value_flag("Dwarf Version", FlagBehavior::Warning, llvm.i32_type().const_int(4, false));
+        let (dibuilder, compile_unit) = md.create_debug_info_builder(
+            true, DWARFSourceLanguage::C, filename, directory, "latte", true, "", 0, "",
+            DWARFEmissionKind::Full, 0, false, false);
+        let di_files = (0..sm.len()).map(|file| {
+            let (filename, directory) = split_path(sm.get_name(file));
+            dibuilder.create_file(filename, directory)
+        }).collect();
+        DebugInfo {llvm, sm, dibuilder, compile_unit, di_files, subprograms: HashMap::new(), scopes: Vec::new()}
+    }
+
+    // File, line and column of the offset.
+    fn get_position(&self, offset: usize) -> (DIFile<'llvm>, u32, u32) {
+        let location = self.sm.get_location(offset);
+        (self.di_files[location.file], location.line as u32, location.column as u32)
+    }
+
+    fn get_basic_type(&self, name: &str, size_in_bits: u64, encoding: u32) -> DIType<'llvm> {
+        self.dibuilder.create_basic_type(name, size_in_bits, encoding, DIFlags::PUBLIC).unwrap().as_type()
+    }
+
+    // Objects are described as pointers, their fields are not.
+    fn get_type(&self, ttype: &Type) -> Option<DIType<'llvm>> {
+        let pointer = |name: &str, pointee| {
+            self.dibuilder.create_pointer_type(name, pointee, 64, 64, AddressSpace::Generic).as_type()
+        };
+        match ttype {
+            Type::Int => Some(self.get_basic_type("int", 32, DW_ATE_SIGNED)),
+            Type::Bool => Some(self.get_basic_type("boolean", 8, DW_ATE_BOOLEAN)),
+            Type::Str => Some(pointer("string", self.get_basic_type("char", 8, DW_ATE_SIGNED_CHAR))),
+            Type::Class(ident) => Some(pointer(ident, self.get_basic_type("char", 8, DW_ATE_SIGNED_CHAR))),
+            Type::Void => None,
+        }
+    }
+
+    pub fn declare_function(&mut self, fnval: FunctionValue<'llvm>, fndef: &FnDef) {
+        let (di_file, line, _) = self.get_position(fndef.span.0);
+        let param_types: Vec<DIType> = fndef.params.iter().filter_map(|p| self.get_type(&p.type_spec.ttype)).collect();
+        let fn_type = self.dibuilder.create_subroutine_type(
+            di_file, self.get_type(&fndef.type_spec.ttype), &param_types, DIFlags::PUBLIC);
+        let subprogram = self.dibuilder.create_function(
+            self.compile_unit.as_debug_info_scope(), &fndef.ident, None, di_file, line, fn_type,
+            false, true, line, DIFlags::PUBLIC, true);
+        fnval.set_subprogram(subprogram);
+        self.subprograms.insert(fndef.ident.clone(), subprogram);
+    }
+
+    pub fn enter_function(&mut self, ident: &Ident) {
+        self.scopes = vec![(self.subprograms[ident], None)];
+    }
+
+    fn get_location(&self, offset: usize) -> DILocation<'llvm> {
+        let (_, line, column) = self.get_position(offset);
+        let (subprogram, inlined_at) = *self.scopes.last().unwrap();
+        self.dibuilder.create_debug_location(self.llvm, line, column, subprogram.as_debug_info_scope(), inlined_at)
+    }
+
+    // Instructions built from now on get the location.
+    pub fn set_location(&self, bd: &Builder<'llvm>, offset: usize) {
+        bd.set_current_debug_location(self.llvm, self.get_location(offset));
+    }
+
+    // Code of an inlined function keeps its own lines, the debugger shows it as called from call_offset.
+    pub fn enter_inlined(&mut self, ident: &Ident, call_offset: usize) {
+        let call_location = self.get_location(call_offset);
+        self.scopes.push((self.subprograms[ident], Some(call_location)));
+    }
+
+    pub fn leave_inlined(&mut self) {
+        self.scopes.pop();
+    }
+
+    // Describes a variable living in the stack slot, arguments are numbered from 1.
+    pub fn declare_variable(&self, slot: PointerValue<'llvm>, ident: &Ident, ttype: &Type, offset: usize,
+                            arg_no: Option<u32>, entry: BasicBlock) {
+        let scope = self.scopes.last().unwrap().0.as_debug_info_scope();
+        let (di_file, line, _) = self.get_position(offset);
+        let di_type = self.get_type(ttype).unwrap();
+        let var = match arg_no {
+            Some(arg_no) => self.dibuilder.create_parameter_variable(scope, ident, arg_no, di_file, line, di_type, true, DIFlags::PUBLIC),
+            None => self.dibuilder.create_auto_variable(scope, ident, di_file, line, di_type, true, DIFlags::PUBLIC, 0),
+        };
+        self.dibuilder.insert_declare_at_end(slot, Some(var), None, self.get_location(offset), entry);
+    }
+
+    // Must be called before the module is verified or written.
+    pub fn finalize(&self) {
+        self.dibuilder.finalize();
+    }
+}
+
+// Name and directory of the file, as DWARF wants them.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = Path::new(path);
+    let filename = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+    let directory = path.parent().and_then(|dir| dir.to_str()).filter(|dir| !dir.is_empty()).unwrap_or(".");
+    (filename, directory)
+}
@@ -1,4 +1,5 @@
-use crate::File;
+use crate::source_map::SourceMap;
+use crate::options::ErrorFormat;
 use crate::latte;
 use crate::ParseError;
 use crate::ast;
@@ -6,22 +7,34 @@ use codespan_reporting::term::{emit, DisplayStyle};
 use codespan_reporting::diagnostic::Label;
 use codespan_reporting::term::Config;
 use codespan_reporting::diagnostic::Diagnostic as Diag;
+use serde_json::json;
 
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
 pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
     pub message: String,
     pub details: Option<(ast::Span, String)>
 }
 
 pub fn gen_no_main() -> Diagnostic {
-    Diagnostic {message: "missing main function".to_owned(), details: None}
+    Diagnostic {code: "E0001", severity: Severity::Error, message: "missing main function".to_owned(), details: None}
 }
 
 pub fn gen_invalid_main() -> Diagnostic {
-    Diagnostic {message: "invalid main function".to_owned(), details: None}
+    Diagnostic {code: "E0002", severity: Severity::Error, message: "invalid main function".to_owned(), details: None}
 }
 
 pub fn gen_multiple_fn_def(ident: &ast::Ident, span: ast::Span) -> Diagnostic {
     Diagnostic {
+        code: "E0003",
+        severity: Severity::Error,
         message: format!("multiple declaration of function {}", ident),
         details: Some((span, "defined second time here".to_owned()))
     }
@@ -29,6 +42,8 @@ pub fn gen_multiple_fn_def(ident: &ast::Ident, span: ast::Span) -> Diagnostic {
 
 pub fn gen_multiple_var_decl(ident: &ast::Ident, span: ast::Span) -> Diagnostic {
     Diagnostic {
+        code: "E0004",
+        severity: Severity::Error,
         message: format!("variable already declared in current scope {}", ident),
         details: Some((span, "second definition here".to_owned()))
     }
@@ -36,6 +51,8 @@ pub fn gen_multiple_var_decl(ident: &ast::Ident, span: ast::Span) -> Diagnostic
 
 pub fn gen_undeclared_variable_in_stmt(ident: &ast::Ident, span: ast::Span) -> Diagnostic {
     Diagnostic {
+        code: "E0005",
+        severity: Severity::Error,
         message: format!("undeclared variable {}", ident),
         details: Some((span, format!("in this statement")))
     }
@@ -43,11 +60,70 @@ pub fn gen_undeclared_variable_in_stmt(ident: &ast::Ident, span: ast::Span) -> D
 
 pub fn gen_invalid_expression_type(expected: &ast::Type, actual: &ast::ExpTypeVal, span: ast::Span) -> Diagnostic {
     Diagnostic {
+        code: "E0006",
+        severity: Severity::Error,
         message: format!("invalid expression type"),
         details: Some((span, format!("expected {}, found {}", expected, actual)))
     }
 }
 
+pub fn gen_import_failed(path: &str, error: &std::io::Error, span: ast::Span) -> Diagnostic {
+    Diagnostic {
+        code: "E0018",
+        severity: Severity::Error,
+        message: format!("cannot import {}: {}", path, error),
+        details: Some((span, "imported here".to_owned()))
+    }
+}
+
+// cycle lists the files on the cycle, starting and ending with the same file
+pub fn gen_import_cycle(cycle: &[&str], span: ast::Span) -> Diagnostic {
+    Diagnostic {
+        code: "E0019",
+        severity: Severity::Error,
+        message: format!("import cycle: {}", cycle.join(" -> ")),
+        details: Some((span, "this import closes the cycle".to_owned()))
+    }
+}
+
+// kind is "function" or "class"
+pub fn gen_conflicting_import(kind: &str, ident: &ast::Ident, span: ast::Span) -> Diagnostic {
+    Diagnostic {
+        code: "E0020",
+        severity: Severity::Error,
+        message: format!("{} {} is imported from two modules or also defined here", kind, ident),
+        details: Some((span, format!("second {} named {}", kind, ident)))
+    }
+}
+
+pub fn gen_not_exported(kind: &str, ident: &ast::Ident, file: &str, span: ast::Span) -> Diagnostic {
+    Diagnostic {
+        code: "E0021",
+        severity: Severity::Error,
+        message: format!("{} {} is not exported by {}", kind, ident, file),
+        details: Some((span, format!("private {} used here", kind)))
+    }
+}
+
+// with -c imported modules must have been compiled before
+pub fn gen_missing_interface(path: &str, reason: &str, span: ast::Span) -> Diagnostic {
+    Diagnostic {
+        code: "E0022",
+        severity: Severity::Error,
+        message: format!("cannot read interface of {}: {}", path, reason),
+        details: Some((span, "imported here, compile it with -c first".to_owned()))
+    }
+}
+
+pub fn gen_unreachable_code(span: ast::Span) -> Diagnostic {
+    Diagnostic {
+        code: "W0001",
+        severity: Severity::Warning,
+        message: "unreachable code".to_owned(),
+        details: Some((span, "this statement is never executed".to_owned()))
+    }
+}
+
 //pub fn gen_invalid_unary(exp: &ast::Exp) -> Diagnostic {
 //    Diagnostic {message: format!("invalid unary exp: {}", exp), details: None }
 //}
@@ -75,29 +151,99 @@ pub fn gen_invalid_expression_type(expected: &ast::Type, actual: &ast::ExpTypeVa
 //    }
 //}
 
-pub fn gen_from_parse_error(err: ParseError) -> Diagnostic {
+// Maps terminals reported by lalrpop (quoted literals and raw regexes) to names readable by humans.
+fn describe_expected_token(token: &str) -> String {
+    if token.starts_with("r#\"") && token.ends_with("\"#") {
+        // depending on the lalrpop version, backslashes of regexes are escaped
+        match token[3..token.len()-2].replace("\\\\", "\\").as_str() {
+            r#"[A-Za-z_'][A-Za-z0-9_']*"# => "identifier".to_owned(),
+            r#"[0-9]+"# => "integer literal".to_owned(),
+            r#"\)\s*null"# => "`)`".to_owned(),
+            regex if regex.starts_with('"') || regex.starts_with("\\\"") => "string literal".to_owned(),
+            regex => format!("`{}`", regex),
+        }
+    }
+    else if token.len() >= 2 && token.starts_with('"') && token.ends_with('"') {
+        format!("`{}`", &token[1..token.len()-1])
+    }
+    else {
+        token.to_owned()
+    }
+}
+
+fn describe_expected(expected: &[String]) -> String {
+    let mut names: Vec<String> = Vec::new();
+    for token in expected {
+        let name = describe_expected_token(token);
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    match names.len() {
+        0 => "nothing".to_owned(),
+        1 => names.pop().unwrap(),
+        _ => format!("one of: {}", names.join(", ")),
+    }
+}
+
+// A statement starting with one of them likely follows a missing `;` also within a line.
+const STATEMENT_KEYWORDS: &[&str] = &["return", "if", "while", "int", "boolean", "string"];
+
+// If the token at pos is the first one in its line or a keyword starting a statement, and the text
+// before it does not end a statement, returns the span of the last character before it.
+fn find_unterminated_statement(source: &str, pos: usize, token: &str) -> Option<ast::Span> {
+    let before = source[..pos].trim_end();
+    if before.is_empty() || !(source[before.len()..pos].contains('\n') || STATEMENT_KEYWORDS.contains(&token)) {
+        return None;
+    }
+    match before.char_indices().last() {
+        Some((_, ';')) | Some((_, '{')) | Some((_, '}')) => None,
+        Some((i, ch)) => Some(ast::Span(i, i + ch.len_utf8())),
+        None => None,
+    }
+}
+
+// source is the text that was given to the parser
+pub fn gen_from_parse_error(err: ParseError, source: &str) -> Diagnostic {
+    let code = match err {
+        ParseError::User{..} => "E0016",
+        _ => "E0015",
+    };
     let ((b, e), comment) = match err {
         ParseError::InvalidToken{location: l} => {
             ((l, l), "invalid token".to_owned())
         },
-        ParseError::UnrecognizedEOF{location: l, ..} => {
-            ((l, l), "unexpected eof".to_owned())
+        ParseError::UnrecognizedEOF{location: l, expected: exp_vec} => {
+            ((l, l), format!("unexpected end of file, expected {}", describe_expected(&exp_vec)))
         },
         ParseError::UnrecognizedToken{token: (b, latte::Token(_, token_str), e), expected: exp_vec} => {
-            ((b, e), format!("unrecognized token: {:?}, expected one of: {:?}", token_str, exp_vec))
+            if exp_vec.iter().any(|t| t == "\";\"") {
+                if let Some(span) = find_unterminated_statement(source, b, token_str) {
+                    return Diagnostic {
+                        code,
+                        severity: Severity::Error,
+                        message: "missing `;` after statement".to_owned(),
+                        details: Some((span, "expected `;` after this".to_owned()))
+                    }
+                }
+            }
+            ((b, e), format!("unexpected `{}`, expected {}", token_str, describe_expected(&exp_vec)))
         },
         ParseError::ExtraToken{token: (b, latte::Token(_, token_str), e)} => {
-            ((b, e), format!("unexpected additional token: {}", token_str))
+            ((b, e), format!("unexpected additional token: `{}`", token_str))
+        },
+        ParseError::User{error} => {
+            return Diagnostic {code, severity: Severity::Error, message: error.message, details: Some((error.span, error.comment))}
         },
-        ParseError::User{error} => ((0, 0), format!("{}", error)),
     };
     if (b, e) == (0, 0) {
-        return Diagnostic {message: comment, details: None}
+        return Diagnostic {code, severity: Severity::Error, message: comment, details: None}
     }
-    Diagnostic {message: "syntax error".to_owned(), details: Some((ast::Span(b, e) , comment))}
+    Diagnostic {code, severity: Severity::Error, message: "syntax error".to_owned(), details: Some((ast::Span(b, e) , comment))}
 }
 
-pub fn print_all(diagnostics: &[Diagnostic], file: &File) {
+// Diagnostics without a span are reported for the first file, the one being compiled.
+pub fn print_all(diagnostics: &[Diagnostic], sm: &SourceMap) {
     let mut stream = termcolor::StandardStream::stderr(termcolor::ColorChoice::Auto);
     let long_cfg = Config::default();
     let short_cfg = {let mut cfg = Config::default(); cfg.display_style = DisplayStyle::Short; cfg};
@@ -105,19 +251,79 @@ pub fn print_all(diagnostics: &[Diagnostic], file: &File) {
     for diagnostic in diagnostics {
         let (diag, config) =
             if let Some((span, comment)) = &diagnostic.details {
-                let span = codespan::Span::new(span.0 as u32, span.1 as u32);
-                let label = Label::new(file.file_id, span, comment);
-                let diag = codespan_reporting::diagnostic::Diagnostic::new_error(diagnostic.message.clone(), label);
+                let (file_id, span) = sm.to_codespan(*span);
+                let label = Label::new(file_id, span, comment);
+                let diag = new_diag(diagnostic, label);
                 let config = &long_cfg;
                 (diag, config)
             }
             else {
-                let span = codespan::Span::new(0, 0);
-                let label = Label::new(file.file_id, span, "");
-                let diag = Diag::new_error(diagnostic.message.clone(), label);
+                let (file_id, span) = sm.to_codespan(ast::Span(0, 0));
+                let label = Label::new(file_id, span, "");
+                let diag = new_diag(diagnostic, label);
                 let config = &short_cfg;
                 (diag, config)
             };
-        emit(&mut stream, config, &file.file_db, &diag).unwrap()
+        emit(&mut stream, config, sm.get_file_db(), &diag).unwrap()
+    }
+}
+
+fn new_diag(diagnostic: &Diagnostic, label: Label) -> Diag {
+    let diag = match diagnostic.severity {
+        Severity::Error => Diag::new_error(diagnostic.message.clone(), label),
+        Severity::Warning => Diag::new_warning(diagnostic.message.clone(), label),
+    };
+    diag.with_code(diagnostic.code)
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl Diagnostic {
+    // Lines and columns are 1-based, byte offsets are 0-based and refer to the source file.
+    pub fn to_json(&self, sm: &SourceMap) -> serde_json::Value {
+        let labels: Vec<serde_json::Value> = self.details.iter().map(|(span, comment)| {
+            let start = sm.get_location(span.0);
+            let end = sm.get_location(span.1);
+            let file_start = sm.get_start(start.file);
+            json!({
+                "file": sm.get_name(start.file),
+                "message": comment,
+                "byte_start": span.0 - file_start,
+                "byte_end": span.1 - file_start,
+                "line_start": start.line,
+                "column_start": start.column,
+                "line_end": end.line,
+                "column_end": end.column,
+            })
+        }).collect();
+        json!({
+            "message": self.message,
+            "code": self.code,
+            "severity": self.severity.as_str(),
+            "labels": labels,
+        })
+    }
+}
+
+// Prints in the format chosen with --error-format.
+pub fn print(diagnostics: &[Diagnostic], sm: &SourceMap, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => print_all(diagnostics, sm),
+        ErrorFormat::Json => print_all_json(diagnostics, sm),
+    }
+}
+
+// Emits one JSON object per line, so the output can be consumed incrementally. They go to stdout,
+// stderr is left to the ERROR or OK line.
+pub fn print_all_json(diagnostics: &[Diagnostic], sm: &SourceMap) {
+    for diagnostic in diagnostics {
+        println!("{}", diagnostic.to_json(sm));
     }
 }
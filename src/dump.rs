@@ -0,0 +1,251 @@
+// Dumps of the ast for debugging, as an indented tree or as JSON.
+// Typed dumps additionally show results of the frontend: types, folded constants and will_return.
+use crate::ast::*;
+use serde_json::{json, Value};
+
+struct TreePrinter {
+    typed: bool,
+    output: String,
+    depth: usize,
+}
+
+impl TreePrinter {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn child<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        self.depth += 1;
+        f(self);
+        self.depth -= 1;
+    }
+
+    fn exp(&mut self, exp_node: &ExpNode) {
+        let label = match &exp_node.exp {
+            Exp::Unary(op, _) => format!("Unary {}", op),
+            Exp::Binary(_, op, _) => format!("Binary {}", op),
+            Exp::Call(ident, _) => format!("Call {}", ident),
+            Exp::Int(v) => format!("Int {}", v),
+            Exp::Bool(v) => format!("Bool {}", v),
+            Exp::Str(v) => format!("Str {:?}", v),
+            Exp::Obj(memloc) => format!("Obj {}", memloc),
+            Exp::Null(ident) => format!("Null {}", ident),
+            Exp::New(ident) => format!("New {}", ident),
+        };
+        match (&exp_node.typeval, self.typed) {
+            (Some(typeval), true) => self.line(&format!("{} : {}", label, typeval.describe())),
+            (None, true) => self.line(&format!("{} : ?", label)),
+            (_, false) => self.line(&label),
+        }
+        self.child(|p| match &exp_node.exp {
+            Exp::Unary(_, inner) => p.exp(inner),
+            Exp::Binary(lexp, _, rexp) => {
+                p.exp(lexp);
+                p.exp(rexp);
+            },
+            Exp::Call(_, args) => args.iter().for_each(|a| p.exp(a)),
+            _ => (),
+        });
+    }
+
+    fn decls(&mut self, label: &str, decls: &VarDecl) {
+        for var in &decls.vars {
+            self.line(&format!("{} {} {}", label, decls.type_spec.ttype, var.ident));
+            if let Some(init) = &var.init {
+                self.child(|p| p.exp(init));
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt_node: &StmtNode) {
+        let label = match &stmt_node.stmt {
+            Stmt::BStmt(_) => format!("Block"),
+            Stmt::Decl(_) => format!("Decl"),
+            Stmt::Ass(memloc, _) => format!("Ass {}", memloc),
+            Stmt::Incr(memloc) => format!("Incr {}", memloc),
+            Stmt::Decr(memloc) => format!("Decr {}", memloc),
+            Stmt::Ret(_) => format!("Ret"),
+            Stmt::VRet => format!("VRet"),
+            Stmt::Cond(..) => format!("Cond"),
+            Stmt::While(..) => format!("While"),
+            Stmt::EStmt(_) => format!("EStmt"),
+            Stmt::Error => format!("Error"),
+        };
+        match (stmt_node.will_return, self.typed) {
+            (Some(will_return), true) => self.line(&format!("{} will_return={}", label, will_return)),
+            (None, true) => self.line(&format!("{} will_return=?", label)),
+            (_, false) => self.line(&label),
+        }
+        self.child(|p| match &stmt_node.stmt {
+            Stmt::BStmt(stmts) => stmts.iter().for_each(|s| p.stmt(s)),
+            Stmt::Decl(decls) => p.decls("Var", decls),
+            Stmt::Ass(_, exp) | Stmt::Ret(exp) | Stmt::EStmt(exp) => p.exp(exp),
+            Stmt::Cond(cond, tstmt, fstmt) => {
+                p.exp(cond);
+                p.stmt(tstmt);
+                if let Some(fstmt) = fstmt {
+                    p.stmt(fstmt);
+                }
+            },
+            Stmt::While(cond, body) => {
+                p.exp(cond);
+                p.stmt(body);
+            },
+            Stmt::Incr(_) | Stmt::Decr(_) | Stmt::VRet | Stmt::Error => (),
+        });
+    }
+
+    fn program(&mut self, prog: &Program) {
+        self.line("Program");
+        self.child(|p| {
+            for import in &prog.imports {
+                p.line(&format!("Import {:?}", import.path));
+            }
+            for class in &prog.classes {
+                p.line(&format!("Class {}{}", class.ident, if class.exported { " exported" } else { "" }));
+                p.child(|p| class.fields.iter().for_each(|f| p.decls("Field", f)));
+            }
+            for fdef in &prog.functions {
+                p.line(&format!("FnDef {} {}{}", fdef.type_spec.ttype, fdef.ident, if fdef.exported { " exported" } else { "" }));
+                p.child(|p| {
+                    fdef.params.iter().for_each(|d| p.decls("Param", d));
+                    p.stmt(&fdef.body);
+                });
+            }
+        });
+    }
+}
+
+// Prints the ast as an indented tree, one node per line.
+pub fn tree(prog: &Program, typed: bool) -> String {
+    let mut printer = TreePrinter {typed, output: String::new(), depth: 0};
+    printer.program(prog);
+    printer.output
+}
+
+fn span_json(span: Span) -> Value {
+    json!({"start": span.0, "end": span.1})
+}
+
+fn type_json(ttype: &Type) -> Value {
+    match ttype {
+        Type::Class(ident) => json!({"class": ident}),
+        _ => json!(ttype.to_string()),
+    }
+}
+
+fn typeval_json(typeval: &Option<ExpTypeVal>) -> Value {
+    match typeval {
+        None => Value::Null,
+        Some(ExpTypeVal::Int(v)) => json!({"type": "int", "value": v}),
+        Some(ExpTypeVal::Bool(v)) => json!({"type": "boolean", "value": v}),
+        Some(ExpTypeVal::Str(v)) => json!({"type": "string", "value": v}),
+        Some(ExpTypeVal::Class(ident)) => json!({"type": {"class": ident}, "value": null}),
+        Some(ExpTypeVal::Void) => json!({"type": "void", "value": null}),
+        Some(ExpTypeVal::Invalid) => json!({"type": "invalid", "value": null}),
+    }
+}
+
+fn field_json(field_node: &FieldNode, typed: bool) -> Value {
+    let mut value = match &field_node.field {
+        Field::Direct(obj, field) => json!({"kind": "Field", "object": {"kind": "Var", "ident": obj}, "field": field}),
+        Field::Indirect(inner, field) => json!({"kind": "Field", "object": field_json(inner, typed), "field": field}),
+    };
+    value["span"] = span_json(field_node.span);
+    if typed {
+        value["typeval"] = typeval_json(&field_node.typeval);
+    }
+    value
+}
+
+fn memloc_json(memloc: &MemLoc, typed: bool) -> Value {
+    match memloc {
+        MemLoc::Var(ident) => json!({"kind": "Var", "ident": ident}),
+        MemLoc::Field(field_node) => field_json(field_node, typed),
+    }
+}
+
+fn exp_json(exp_node: &ExpNode, typed: bool) -> Value {
+    let mut value = match &exp_node.exp {
+        Exp::Unary(op, inner) => json!({"kind": "Unary", "op": op.to_string(), "exp": exp_json(inner, typed)}),
+        Exp::Binary(lexp, op, rexp) => json!({
+            "kind": "Binary", "op": op.to_string(), "lhs": exp_json(lexp, typed), "rhs": exp_json(rexp, typed),
+        }),
+        Exp::Call(ident, args) => json!({
+            "kind": "Call", "ident": ident, "args": args.iter().map(|a| exp_json(a, typed)).collect::<Vec<_>>(),
+        }),
+        Exp::Int(v) => json!({"kind": "Int", "value": v}),
+        Exp::Bool(v) => json!({"kind": "Bool", "value": v}),
+        Exp::Str(v) => json!({"kind": "Str", "value": v}),
+        Exp::Obj(memloc) => json!({"kind": "Obj", "memloc": memloc_json(memloc, typed)}),
+        Exp::Null(ident) => json!({"kind": "Null", "class": ident}),
+        Exp::New(ident) => json!({"kind": "New", "class": ident}),
+    };
+    value["span"] = span_json(exp_node.span);
+    if typed {
+        value["typeval"] = typeval_json(&exp_node.typeval);
+    }
+    value
+}
+
+fn decls_json(decls: &VarDecl, typed: bool) -> Value {
+    let vars: Vec<Value> = decls.vars.iter().map(|var| json!({
+        "ident": var.ident,
+        "span": span_json(var.span),
+        "init": var.init.as_ref().map(|e| exp_json(e, typed)),
+    })).collect();
+    json!({"type": type_json(&decls.type_spec.ttype), "span": span_json(decls.span), "vars": vars})
+}
+
+fn stmt_json(stmt_node: &StmtNode, typed: bool) -> Value {
+    let mut value = match &stmt_node.stmt {
+        Stmt::BStmt(stmts) => json!({"kind": "BStmt", "stmts": stmts.iter().map(|s| stmt_json(s, typed)).collect::<Vec<_>>()}),
+        Stmt::Decl(decls) => json!({"kind": "Decl", "decl": decls_json(decls, typed)}),
+        Stmt::Ass(memloc, exp) => json!({"kind": "Ass", "memloc": memloc_json(memloc, typed), "exp": exp_json(exp, typed)}),
+        Stmt::Incr(memloc) => json!({"kind": "Incr", "memloc": memloc_json(memloc, typed)}),
+        Stmt::Decr(memloc) => json!({"kind": "Decr", "memloc": memloc_json(memloc, typed)}),
+        Stmt::Ret(exp) => json!({"kind": "Ret", "exp": exp_json(exp, typed)}),
+        Stmt::VRet => json!({"kind": "VRet"}),
+        Stmt::Cond(cond, tstmt, fstmt) => json!({
+            "kind": "Cond",
+            "cond": exp_json(cond, typed),
+            "then": stmt_json(tstmt, typed),
+            "else": fstmt.as_ref().map(|s| stmt_json(s, typed)),
+        }),
+        Stmt::While(cond, body) => json!({"kind": "While", "cond": exp_json(cond, typed), "body": stmt_json(body, typed)}),
+        Stmt::EStmt(exp) => json!({"kind": "EStmt", "exp": exp_json(exp, typed)}),
+        Stmt::Error => json!({"kind": "Error"}),
+    };
+    value["span"] = span_json(stmt_node.span);
+    if typed {
+        value["will_return"] = json!(stmt_node.will_return);
+    }
+    value
+}
+
+// Converts the ast to JSON, every node has "kind" and "span" (byte offsets).
+pub fn json(prog: &Program, typed: bool) -> Value {
+    let classes: Vec<Value> = prog.classes.iter().map(|class| json!({
+        "ident": class.ident,
+        "span": span_json(class.span),
+        "exported": class.exported,
+        "fields": class.fields.iter().map(|f| decls_json(f, typed)).collect::<Vec<_>>(),
+    })).collect();
+    let functions: Vec<Value> = prog.functions.iter().map(|fdef| json!({
+        "ident": fdef.ident,
+        "span": span_json(fdef.span),
+        "exported": fdef.exported,
+        "type": type_json(&fdef.type_spec.ttype),
+        "params": fdef.params.iter().map(|d| decls_json(d, typed)).collect::<Vec<_>>(),
+        "body": stmt_json(&fdef.body, typed),
+    })).collect();
+    let imports: Vec<Value> = prog.imports.iter().map(|import| json!({
+        "path": import.path,
+        "span": span_json(import.span),
+    })).collect();
+    json!({"imports": imports, "classes": classes, "functions": functions})
+}
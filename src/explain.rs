@@ -0,0 +1,407 @@
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+pub fn get(code: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES.iter().find(|c| c.code == code)
+}
+
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {code: "E0001", title: "missing main function", explanation: r#"
+Every Latte program must define a function named `main`.
+
+Erroneous code example:
+
+    int foo() {
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0002", title: "invalid main function", explanation: r#"
+The `main` function must return `int` and take no arguments.
+
+Erroneous code example:
+
+    void main(int argc) {
+        printInt(argc);
+    }
+
+Corrected example:
+
+    int main() {
+        printInt(42);
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0003", title: "multiple declaration of function", explanation: r#"
+A function with the same name was defined more than once. Function names share
+one namespace with the builtin functions (printInt, printString, readInt, ...).
+
+Erroneous code example:
+
+    int f() { return 1; }
+    int f() { return 2; }
+
+Corrected example:
+
+    int f() { return 1; }
+    int g() { return 2; }
+"#},
+    ErrorCode {code: "E0004", title: "variable already declared in current scope", explanation: r#"
+A variable (or a function parameter) was declared twice in the same block.
+Shadowing is allowed only in a nested block.
+
+Erroneous code example:
+
+    int main() {
+        int x;
+        int x;
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        int x;
+        {
+            int x;
+        }
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0005", title: "undeclared variable", explanation: r#"
+A variable was used before it was declared, or it is not visible in the current
+scope.
+
+Erroneous code example:
+
+    int main() {
+        x = 14;
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        int x;
+        x = 14;
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0006", title: "invalid expression type", explanation: r#"
+An expression has a different type than required by its context, e.g. a value
+assigned to a variable, a condition or an initializer.
+
+Erroneous code example:
+
+    int main() {
+        int x = "";
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        string x = "";
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0007", title: "no such field", explanation: r#"
+A field was accessed that is not declared in the class.
+
+Erroneous code example:
+
+    class Point { int x; int y; }
+
+    int main() {
+        Point p = new Point;
+        printInt(p.z);
+        return 0;
+    }
+
+Corrected example:
+
+    class Point { int x; int y; }
+
+    int main() {
+        Point p = new Point;
+        printInt(p.y);
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0008", title: "unknown class", explanation: r#"
+A class name was used that is not defined anywhere in the program.
+
+Erroneous code example:
+
+    int main() {
+        Node n = new Node;
+        return 0;
+    }
+
+Corrected example:
+
+    class Node { int value; }
+
+    int main() {
+        Node n = new Node;
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0009", title: "invalid use of . (dot) operator", explanation: r#"
+The . (dot) operator can be applied only to objects.
+
+Erroneous code example:
+
+    int main() {
+        int x;
+        printInt(x.y);
+        return 0;
+    }
+
+Corrected example:
+
+    class Box { int y; }
+
+    int main() {
+        Box x = new Box;
+        printInt(x.y);
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0010", title: "invalid use of operator", explanation: r#"
+An operator was applied to operands of unsupported types. Arithmetic works on
+ints, `+` additionally concatenates strings, logical operators work on booleans
+and both sides of a comparison must have the same type.
+
+Erroneous code example:
+
+    int main() {
+        string x;
+        x = "pi" + 1;
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        string x;
+        x = "pi" + "1";
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0011", title: "unknown function", explanation: r#"
+A function was called that is neither defined in the program nor a builtin.
+
+Erroneous code example:
+
+    int main() {
+        prnitInt(1);
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        printInt(1);
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0012", title: "invalid argument types", explanation: r#"
+A function was called with a wrong number of arguments or with arguments of
+wrong types.
+
+Erroneous code example:
+
+    int main() {
+        printInt("foo");
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        printString("foo");
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0013", title: "invalid return type", explanation: r#"
+A return statement returns a value of a different type than declared by the
+function, or returns nothing from a non-void function.
+
+Erroneous code example:
+
+    int main() {
+        return true;
+    }
+
+Corrected example:
+
+    int main() {
+        return 1;
+    }
+"#},
+    ErrorCode {code: "E0014", title: "missing return statement", explanation: r#"
+Execution of a non-void function may reach the end of its body without
+returning a value. The end is not reachable after a return statement, a
+`while (true)` loop or a call to `error()`, conditions are evaluated only
+if their value is known at compile time.
+
+Erroneous code example:
+
+    int main() {
+        if (false)
+            return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        if (false)
+            return 0;
+        return 1;
+    }
+"#},
+    ErrorCode {code: "E0015", title: "syntax error", explanation: r#"
+The source code does not conform to the Latte grammar.
+
+Erroneous code example:
+
+    int main)( {
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        return 0;
+    }
+
+Keywords cannot be used as names of variables, functions or classes. Besides
+types and statements (`int`, `while`, `return`, ...) these are `class`, `new`,
+`true`, `false` and, since modules were added, `import` and `export`:
+
+    int import = 1;
+"#},
+    ErrorCode {code: "E0016", title: "invalid integer literal", explanation: r#"
+An integer literal does not fit into the `int` type (32-bit signed integer).
+
+Erroneous code example:
+
+    int main() {
+        printInt(9999999999);
+        return 0;
+    }
+
+Corrected example:
+
+    int main() {
+        printInt(999999999);
+        return 0;
+    }
+"#},
+    ErrorCode {code: "E0017", title: "code generation failed", explanation: r#"
+The program was accepted by the frontend but LLVM rejected the generated code.
+This is a bug in the compiler, please report it together with the program.
+"#},
+    ErrorCode {code: "E0018", title: "cannot import file", explanation: r#"
+An imported file does not exist or cannot be read. Paths of imports are
+relative to the directory of the importing file.
+
+Erroneous code example:
+
+    import "lists.lat";
+
+Corrected example (with the file named list.lat next to the importing one):
+
+    import "list.lat";
+"#},
+    ErrorCode {code: "E0019", title: "import cycle", explanation: r#"
+Files import each other, directly or through other files. Modules must form
+an acyclic graph, move the definitions used by both files into a third one.
+
+Erroneous code example:
+
+    // a.lat
+    import "b.lat";
+
+    // b.lat
+    import "a.lat";
+"#},
+    ErrorCode {code: "E0020", title: "conflicting imported name", explanation: r#"
+A function or class exported by an imported module has the same name as a
+definition of the importing module or of another imported module.
+
+Erroneous code example:
+
+    // list.lat
+    export int size() { return 0; }
+
+    // main.lat
+    import "list.lat";
+    int size() { return 1; }
+
+Corrected example:
+
+    // main.lat
+    import "list.lat";
+    int mySize() { return 1; }
+"#},
+    ErrorCode {code: "E0021", title: "definition not exported", explanation: r#"
+A function or class of an imported module is used, but it is private to the
+module. Only definitions marked with `export` are visible in importing files.
+
+Erroneous code example:
+
+    // list.lat
+    int size() { return 0; }
+
+Corrected example:
+
+    // list.lat
+    export int size() { return 0; }
+"#},
+    ErrorCode {code: "E0022", title: "missing interface of imported module", explanation: r#"
+A file compiled separately with `-c` imports a module whose interface file
+(.lati) is missing, unreadable or older than the module's source. Imported
+modules are not parsed in this mode, compile them with `-c` first, in the
+order of imports.
+
+Erroneous commands:
+
+    latte -c main.lat    # main.lat imports list.lat, list.lati does not exist
+
+Corrected commands:
+
+    latte -c list.lat
+    latte -c main.lat
+    latte link -o main.bc list.bc main.bc
+"#},
+    ErrorCode {code: "W0001", title: "unreachable code", explanation: r#"
+A statement follows a return statement, a `while (true)` loop or a call to
+`error()` in the same block, so it is never executed. It is type checked, but
+not compiled. This is a warning, it fails the compilation only with
+`--warnings=deny`.
+
+Erroneous code example:
+
+    int main() {
+        return 0;
+        printInt(1);
+    }
+
+Corrected example:
+
+    int main() {
+        printInt(1);
+        return 0;
+    }
+"#},
+];
@@ -0,0 +1,317 @@
+// Pretty printer producing canonical Latte source, used by `latte fmt`.
+use crate::ast::*;
+use crate::diag;
+use crate::parse;
+use crate::utils::{escape_string, extract_comments};
+
+const INDENT: &str = "    ";
+
+// Operator precedence levels as in latte.lalrpop, higher binds tighter.
+const PREC_OR: u8 = 1;
+const PREC_AND: u8 = 2;
+const PREC_REL: u8 = 3;
+const PREC_ADD: u8 = 4;
+const PREC_MUL: u8 = 5;
+const PREC_UNARY: u8 = 6;
+
+fn binary_prec(op: &BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Or => PREC_OR,
+        BinaryOp::And => PREC_AND,
+        BinaryOp::Eq | BinaryOp::Neq | BinaryOp::Gt | BinaryOp::Gte | BinaryOp::Lt | BinaryOp::Lte => PREC_REL,
+        BinaryOp::Add | BinaryOp::Sub => PREC_ADD,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => PREC_MUL,
+    }
+}
+
+fn exp_prec(exp: &Exp) -> u8 {
+    match exp {
+        Exp::Binary(_, op, _) => binary_prec(op),
+        _ => PREC_UNARY,
+    }
+}
+
+fn type_name(ttype: &Type) -> String {
+    match ttype {
+        Type::Class(ident) => ident.clone(),
+        _ => ttype.to_string(),
+    }
+}
+
+// Prints the expression, parenthesized if it binds weaker than min_prec.
+fn exp_text(exp_node: &ExpNode, min_prec: u8) -> String {
+    let text = match &exp_node.exp {
+        Exp::Binary(lexp, op, rexp) => {
+            let prec = binary_prec(op);
+            // || and && are right associative, the other operators are left associative
+            let (lmin, rmin) = match op {
+                BinaryOp::Or | BinaryOp::And => (prec + 1, prec),
+                _ => (prec, prec + 1),
+            };
+            format!("{} {} {}", exp_text(lexp, lmin), op, exp_text(rexp, rmin))
+        },
+        Exp::Unary(op, inner) => {
+            let inner_text = exp_text(inner, PREC_UNARY);
+            // "--" would be lexed as decrement
+            if *op == UnaryOp::Neg && inner_text.starts_with('-') {
+                format!("{} {}", op, inner_text)
+            } else {
+                format!("{}{}", op, inner_text)
+            }
+        },
+        Exp::Call(ident, args) => {
+            let args: Vec<String> = args.iter().map(|a| exp_text(a, PREC_OR)).collect();
+            format!("{}({})", ident, args.join(", "))
+        },
+        Exp::Int(v) => v.to_string(),
+        Exp::Bool(v) => v.to_string(),
+        Exp::Str(v) => format!("\"{}\"", escape_string(v)),
+        Exp::Obj(memloc) => memloc.to_string(),
+        Exp::Null(ident) => format!("({}) null", ident),
+        Exp::New(ident) => format!("new {}", ident),
+    };
+    if exp_prec(&exp_node.exp) < min_prec {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+fn decl_text(decl: &VarDecl) -> String {
+    let vars: Vec<String> = decl.vars.iter().map(|var| match &var.init {
+        Some(init) => format!("{} = {}", var.ident, exp_text(init, PREC_OR)),
+        None => var.ident.clone(),
+    }).collect();
+    format!("{} {}", type_name(&decl.type_spec.ttype), vars.join(", "))
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<(usize, String)>,
+    next_comment: usize,
+    output: String,
+    indent: usize,
+    last_end: usize, // offset in the source where the last printed item ends
+    block_start: bool, // nothing was printed in the current block yet
+    after_def: bool, // the last printed item is a top level definition
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str) -> Self {
+        Formatter {
+            source,
+            comments: extract_comments(source),
+            next_comment: 0,
+            output: String::new(),
+            indent: 0,
+            last_end: 0,
+            block_start: true,
+            after_def: false,
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn starts_block(&self, span: Span) -> bool {
+        self.source[span.0..].starts_with('{')
+    }
+
+    // Emits a blank line before an item if there was one in the source.
+    fn separate(&mut self, start: usize) {
+        let blank_line = start > self.last_end && self.source[self.last_end..start].matches('\n').count() >= 2;
+        if !self.block_start && (blank_line || self.after_def) {
+            self.output.push('\n');
+        }
+        self.block_start = false;
+        self.after_def = false;
+    }
+
+    // Prints comments placed before the given offset. Comments on the same line as the
+    // previous item (or inside it) are kept at the end of its line.
+    fn flush_comments(&mut self, until: usize) {
+        while self.next_comment < self.comments.len() && self.comments[self.next_comment].0 < until {
+            let (start, text) = self.comments[self.next_comment].clone();
+            self.next_comment += 1;
+            let text = text.trim_end();
+            let trailing = !self.output.is_empty()
+                && (start < self.last_end || !self.source[self.last_end..start].contains('\n'));
+            if trailing {
+                self.output.pop();
+                self.output.push(' ');
+                self.output.push_str(text);
+                self.output.push('\n');
+            } else {
+                self.separate(start);
+                self.line(text);
+            }
+            self.last_end = self.last_end.max(start + text.len());
+        }
+    }
+
+    // Prints statements of a block, without the braces.
+    fn block(&mut self, stmts: &[Box<StmtNode>], end: usize) {
+        self.indent += 1;
+        self.block_start = true;
+        for stmt in stmts {
+            self.flush_comments(stmt.span.0);
+            self.separate(stmt.span.0);
+            self.stmt(stmt);
+            self.last_end = stmt.span.1;
+        }
+        self.flush_comments(end);
+        self.indent -= 1;
+        self.block_start = false;
+    }
+
+    // Prints the body of if/while, which is always wrapped in a block by the parser.
+    fn body(&mut self, wrapper: &StmtNode) {
+        if let Stmt::BStmt(stmts) = &wrapper.stmt {
+            if let [inner] = stmts.as_slice() {
+                if let Stmt::BStmt(inner_stmts) = &inner.stmt {
+                    if self.starts_block(inner.span) {
+                        self.last_end = inner.span.0 + 1;
+                        self.block(inner_stmts, inner.span.1 - 1);
+                    } else {
+                        // empty statement
+                        self.block(&[], inner.span.1);
+                    }
+                    self.last_end = inner.span.1;
+                    return;
+                }
+            }
+            self.block(stmts, wrapper.span.1);
+            self.last_end = wrapper.span.1;
+        }
+    }
+
+    fn cond(&mut self, cond: &ExpNode, tstmt: &StmtNode, fstmt: &Option<Box<StmtNode>>, prefix: &str) {
+        self.line(&format!("{}if ({}) {{", prefix, exp_text(cond, PREC_OR)));
+        self.body(tstmt);
+        match fstmt {
+            None => self.line("}"),
+            Some(fstmt) => {
+                if let Stmt::BStmt(stmts) = &fstmt.stmt {
+                    if let [inner] = stmts.as_slice() {
+                        if let Stmt::Cond(cond, tstmt, fstmt) = &inner.stmt {
+                            return self.cond(cond, tstmt, fstmt, "} else ");
+                        }
+                    }
+                }
+                self.line("} else {");
+                self.body(fstmt);
+                self.line("}");
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt_node: &StmtNode) {
+        match &stmt_node.stmt {
+            Stmt::BStmt(stmts) => {
+                if self.starts_block(stmt_node.span) {
+                    self.line("{");
+                    self.last_end = stmt_node.span.0 + 1;
+                    self.block(stmts, stmt_node.span.1 - 1);
+                    self.line("}");
+                } else {
+                    self.line(";");
+                }
+            },
+            Stmt::Decl(decl) => self.line(&format!("{};", decl_text(decl))),
+            Stmt::Ass(memloc, exp) => self.line(&format!("{} = {};", memloc, exp_text(exp, PREC_OR))),
+            Stmt::Incr(memloc) => self.line(&format!("{}++;", memloc)),
+            Stmt::Decr(memloc) => self.line(&format!("{}--;", memloc)),
+            Stmt::Ret(exp) => self.line(&format!("return {};", exp_text(exp, PREC_OR))),
+            Stmt::VRet => self.line("return;"),
+            Stmt::Cond(cond, tstmt, fstmt) => self.cond(cond, tstmt, fstmt, ""),
+            Stmt::While(cond, body) => {
+                self.line(&format!("while ({}) {{", exp_text(cond, PREC_OR)));
+                self.body(body);
+                self.line("}");
+            },
+            Stmt::EStmt(exp) => self.line(&format!("{};", exp_text(exp, PREC_OR))),
+            Stmt::Error => {
+                let text = self.source[stmt_node.span.0..stmt_node.span.1].trim();
+                self.line(text);
+            },
+        }
+    }
+
+    fn fn_def(&mut self, fdef: &FnDef) {
+        let params: Vec<String> = fdef.params.iter().map(decl_text).collect();
+        let export = if fdef.exported { "export " } else { "" };
+        self.line(&format!("{}{} {}({}) {{", export, type_name(&fdef.type_spec.ttype), fdef.ident, params.join(", ")));
+        self.last_end = fdef.body.span.0 + 1;
+        if let Stmt::BStmt(stmts) = &fdef.body.stmt {
+            self.block(stmts, fdef.body.span.1 - 1);
+        }
+        self.line("}");
+    }
+
+    fn class_def(&mut self, class: &ClassDef) {
+        let export = if class.exported { "export " } else { "" };
+        self.line(&format!("{}class {} {{", export, class.ident));
+        self.last_end = self.source[class.span.0..].find('{').map_or(class.span.0, |i| class.span.0 + i + 1);
+        self.indent += 1;
+        self.block_start = true;
+        for field in &class.fields {
+            self.flush_comments(field.span.0);
+            self.separate(field.span.0);
+            self.line(&format!("{};", decl_text(field)));
+            self.last_end = field.span.1;
+        }
+        self.flush_comments(class.span.1 - 1);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn program(&mut self, prog: &Program) {
+        // imports are kept together, separated from definitions by a blank line
+        for import in &prog.imports {
+            self.flush_comments(import.span.0);
+            self.separate(import.span.0);
+            self.line(&format!("import \"{}\";", escape_string(&import.path)));
+            self.last_end = import.span.1;
+        }
+        self.after_def = !prog.imports.is_empty();
+
+        // functions and classes are kept in separate lists, restore the source order
+        let mut defs: Vec<(Span, Def)> = Vec::new();
+        defs.extend(prog.functions.iter().map(|f| (f.span, Def::Fn(f))));
+        defs.extend(prog.classes.iter().map(|c| (c.span, Def::Class(c))));
+        defs.sort_by_key(|(span, _)| span.0);
+
+        for (span, def) in defs {
+            self.flush_comments(span.0);
+            self.separate(span.0);
+            match def {
+                Def::Fn(fdef) => self.fn_def(fdef),
+                Def::Class(class) => self.class_def(class),
+            }
+            self.last_end = span.1;
+            self.after_def = true;
+        }
+        self.flush_comments(usize::max_value());
+    }
+}
+
+enum Def<'a> {
+    Fn(&'a FnDef),
+    Class(&'a ClassDef),
+}
+
+// Formats the source, fails if it contains syntax errors.
+pub fn format_source(source: &str) -> Result<String, Vec<diag::Diagnostic>> {
+    let prog = match parse(source) {
+        (Some(prog), diags) if diags.is_empty() => prog,
+        (_, diags) => return Err(diags),
+    };
+    let mut formatter = Formatter::new(source);
+    formatter.program(&prog);
+    Ok(formatter.output)
+}
@@ -1,23 +1,27 @@
 use crate::ast::*;
 use crate::diag;
+use crate::modules::AstMut;
 use crate::scoped_map::ScopedMap;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::ops::DerefMut;
 
-type Env = ScopedMap<Ident, Type>;
+type Env = ScopedMap<Ident, (Type, ExpTypeVal)>; // type and value known at the current point
 type FEnv = HashMap<Ident, (Type, Vec<Type>)>;
 type Diags = Vec<diag::Diagnostic>;
 
 type ClassDesc = HashMap<Ident, Type>;
 
+// builtins which never return to the caller
+const DIVERGING_FUNCTIONS: &[&str] = &["error"];
+
 #[derive(Debug)]
 struct CEnv {
     classes: HashMap<Ident, ClassDesc>
 }
 
 impl CEnv {
-    fn new(class_list: &Vec<ClassDef>) -> Self {
+    fn new(class_list: &Vec<ClassDef>, externs: &[ExternClass]) -> Self {
         let mut classes = HashMap::new();
         for class in class_list.iter() {
             let mut class_desc = HashMap::new();
@@ -26,6 +30,9 @@ impl CEnv {
             }
             classes.insert(class.ident.clone(), class_desc);
         }
+        for class in externs {
+            classes.insert(class.symbol.clone(), class.fields.iter().cloned().collect());
+        }
         CEnv{classes}
     }
 
@@ -41,7 +48,8 @@ impl CEnv {
 
 fn get_unary_op_typeval(op: &UnaryOp, typeval: &ExpTypeVal) -> ExpTypeVal {
     match (op, typeval) {
-        (UnaryOp::Neg, ExpTypeVal::Int(Some(v))) => ExpTypeVal::Int(Some(-*v)),
+        // folding is exact, overflowing expressions are left to the runtime checks (see --overflow)
+        (UnaryOp::Neg, ExpTypeVal::Int(Some(v))) => ExpTypeVal::Int(v.checked_neg()),
         (UnaryOp::Not, ExpTypeVal::Bool(Some(v))) => ExpTypeVal::Bool(Some(!*v)),
         (UnaryOp::Neg, ExpTypeVal::Int(_)) =>  ExpTypeVal::Int(None),
         (UnaryOp::Not, ExpTypeVal::Bool(_)) => ExpTypeVal::Bool(None),
@@ -63,11 +71,12 @@ fn get_binary_op_typeval(op: &BinaryOp, ltypeval: &ExpTypeVal, rtypeval: &ExpTyp
         (BinaryOp::Lt,  ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Bool(Some(*l < *r)),
         (BinaryOp::Lte, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Bool(Some(*l <= *r)),
 
-        (BinaryOp::Add, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Int(Some(*l + *r)),
-        (BinaryOp::Sub, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Int(Some(*l - *r)),
-        (BinaryOp::Mul, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Int(Some(*l * *r)),
-        (BinaryOp::Mod, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r))) if *r != 0 => ExpTypeVal::Int(Some(*l % *r)),
-        (BinaryOp::Div, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r))) if *r != 0 => ExpTypeVal::Int(Some(*l / *r)),
+        (BinaryOp::Add, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Int(l.checked_add(*r)),
+        (BinaryOp::Sub, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Int(l.checked_sub(*r)),
+        (BinaryOp::Mul, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r)))  => ExpTypeVal::Int(l.checked_mul(*r)),
+        // division by zero and INT_MIN / -1 are not folded
+        (BinaryOp::Mod, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r))) => ExpTypeVal::Int(l.checked_rem(*r)),
+        (BinaryOp::Div, ExpTypeVal::Int(Some(l)),  ExpTypeVal::Int(Some(r))) => ExpTypeVal::Int(l.checked_div(*r)),
 
         (BinaryOp::Eq,  ExpTypeVal::Bool(_),  ExpTypeVal::Bool(_)) => ExpTypeVal::Bool(None),
         (BinaryOp::Eq,  ExpTypeVal::Int(_),   ExpTypeVal::Int(_))  => ExpTypeVal::Bool(None),
@@ -104,6 +113,8 @@ fn verify_class_field(ttype: &Type, field_name: &Ident, cenv: &CEnv, diags: &mut
                 ExpTypeVal::from_type(&field_type)
             } else {
                 diags.push(diag::Diagnostic{
+                    code: "E0007",
+                    severity: diag::Severity::Error,
                     message: format!("class \"{}\" does not have \"{}\" field", class_name, field_name),
                     details: Some((span, format!("no such field")))
                 });
@@ -112,6 +123,8 @@ fn verify_class_field(ttype: &Type, field_name: &Ident, cenv: &CEnv, diags: &mut
         }
         else {
             diags.push(diag::Diagnostic{
+                code: "E0008",
+                severity: diag::Severity::Error,
                 message: format!("undeclared class \"{}\"", class_name),
                 details: Some((span, format!("in this expression")))
             });
@@ -120,6 +133,8 @@ fn verify_class_field(ttype: &Type, field_name: &Ident, cenv: &CEnv, diags: &mut
     }
     else {
         diags.push(diag::Diagnostic{
+            code: "E0009",
+            severity: diag::Severity::Error,
             message: format!("invalid use of . (dot) operator"),
             details: Some((span, format!("this is not a class")))
         });
@@ -130,11 +145,13 @@ fn verify_class_field(ttype: &Type, field_name: &Ident, cenv: &CEnv, diags: &mut
 fn verify_object_field(field: &mut FieldNode, cenv: &CEnv, env: &Env, diags: &mut Diags) {
     field.typeval = Some(match &mut field.field {
         Field::Direct(obj_name, field_name) => {
-            if let Some(ttype) = env.get(obj_name) {
+            if let Some((ttype, _)) = env.get(obj_name) {
                 // left side is in environment
                 verify_class_field(ttype, field_name, cenv, diags, field.span)
             } else {
                 diags.push(diag::Diagnostic{
+                    code: "E0005",
+                    severity: diag::Severity::Error,
                     message: format!("object not found in current scope: {}", obj_name),
                     details: Some((field.span, format!("in this place")))
                 });
@@ -164,6 +181,8 @@ fn verify_exp(exp_node: &mut ExpNode, fenv: &FEnv, cenv: &CEnv, env: &Env, diags
             let typeval = get_unary_op_typeval(op, inner_tv);
             if inner_tv.has_valid_type() && !typeval.has_valid_type() {
                 diags.push(diag::Diagnostic{
+                    code: "E0010",
+                    severity: diag::Severity::Error,
                     message: format!("invalid use of operand {}", op),
                     details: Some((inner.span, format!("type mismatch: {} {}", op, &inner_tv)))
                 });
@@ -178,6 +197,8 @@ fn verify_exp(exp_node: &mut ExpNode, fenv: &FEnv, cenv: &CEnv, env: &Env, diags
             let typeval = get_binary_op_typeval(op, ltv, rtv);
             if ltv.has_valid_type() && rtv.has_valid_type() && !typeval.has_valid_type() {
                 diags.push(diag::Diagnostic{
+                    code: "E0010",
+                    severity: diag::Severity::Error,
                     message: format!("invalid use of operand {}", op),
                     details: Some((exp_node.span, format!("type mismatch: {} {} {}", &ltv, op, &rtv)))
                 });
@@ -188,6 +209,8 @@ fn verify_exp(exp_node: &mut ExpNode, fenv: &FEnv, cenv: &CEnv, env: &Env, diags
             match fenv.get(ident.as_str()) {
                 None => {
                     diags.push(diag::Diagnostic{
+                        code: "E0011",
+                        severity: diag::Severity::Error,
                         message: format!("unknown function identifier {}", ident),
                         details: Some((exp_node.span, format!("in this expression")))
                     });
@@ -208,6 +231,8 @@ fn verify_exp(exp_node: &mut ExpNode, fenv: &FEnv, cenv: &CEnv, env: &Env, diags
                         // all arguments has valid type, check if it matches with the signature
                         if param_types.ne(&arg_types) {
                             diags.push(diag::Diagnostic {
+                                code: "E0012",
+                                severity: diag::Severity::Error,
                                 message: format!("invalid argument types"),
                                 details: Some((exp_node.span, format!("expected {f}({p:?}), found {f}({a:?})", f=ident, p=param_types, a=arg_types)))
                             })
@@ -221,12 +246,14 @@ fn verify_exp(exp_node: &mut ExpNode, fenv: &FEnv, cenv: &CEnv, env: &Env, diags
             match env.get(&ident) {
                 None => {
                     diags.push(diag::Diagnostic {
+                        code: "E0005",
+                        severity: diag::Severity::Error,
                         message: format!("undeclared variable {}", ident),
                         details: Some((exp_node.span, format!("in this expression")))
                     });
                     Some(ExpTypeVal::Invalid)
                 } ,
-                Some(vtype) => Some(ExpTypeVal::from_type(vtype)),
+                Some((_, value)) => Some(value.clone()),
             }
         },
         Exp::Obj(MemLoc::Field(field)) => {
@@ -242,6 +269,8 @@ fn verify_exp(exp_node: &mut ExpNode, fenv: &FEnv, cenv: &CEnv, env: &Env, diags
             }
             else {
                 diags.push(diag::Diagnostic{
+                    code: "E0008",
+                    severity: diag::Severity::Error,
                     message: format!("unknown class name"),
                     details: Some((exp_node.span, format!("in this expression"))),
                 });
@@ -251,20 +280,100 @@ fn verify_exp(exp_node: &mut ExpNode, fenv: &FEnv, cenv: &CEnv, env: &Env, diags
     }
 }
 
+// Value of a variable declared without initializer.
+fn default_typeval(ttype: &Type) -> ExpTypeVal {
+    match ttype {
+        Type::Int => ExpTypeVal::Int(Some(0)),
+        Type::Bool => ExpTypeVal::Bool(Some(false)),
+        Type::Str => ExpTypeVal::Str(Some(String::new())),
+        _ => ExpTypeVal::from_type(ttype),
+    }
+}
+
+fn declare_var(ident: &Ident, ttype: &Type, value: ExpTypeVal, span: Span, env: &mut Env, diags: &mut Diags) {
+    if env.insert_into_top_scope(ident.clone(), (ttype.clone(), value)).is_some() {
+        diags.push(diag::gen_multiple_var_decl(ident, span));
+    }
+}
+
+// Sets a new value of the variable, its value becomes unknown if the type does not match.
+fn assign_var(ident: &Ident, value: &ExpTypeVal, env: &mut Env) {
+    if let Some((ttype, _)) = env.get(ident) {
+        let ttype = ttype.clone();
+        let value = if value.has_type(&ttype) { value.clone() } else { ExpTypeVal::from_type(&ttype) };
+        env.replace_topmost(ident.clone(), (ttype, value));
+    }
+}
+
+fn forget_var(ident: &Ident, env: &mut Env) {
+    if let Some((ttype, _)) = env.get(ident) {
+        let unknown = ExpTypeVal::from_type(ttype);
+        assign_var(ident, &unknown, env);
+    }
+}
+
+// Forgets values which differ from the other state, used where two control flow paths join.
+fn merge_values(env: &mut Env, other: &Env) {
+    let idents: Vec<Ident> = env.keys().cloned().collect();
+    for ident in idents {
+        if env.get(&ident).map(|(_, v)| v) != other.get(&ident).map(|(_, v)| v) {
+            forget_var(&ident, env);
+        }
+    }
+}
+
+// Collects variables assigned anywhere in the statement.
+fn collect_assigned_vars(stmt_node: &StmtNode, result: &mut Vec<Ident>) {
+    match &stmt_node.stmt {
+        Stmt::BStmt(stmts) => stmts.iter().for_each(|s| collect_assigned_vars(s, result)),
+        Stmt::Ass(MemLoc::Var(ident), _) | Stmt::Incr(MemLoc::Var(ident)) | Stmt::Decr(MemLoc::Var(ident)) => {
+            result.push(ident.clone())
+        },
+        Stmt::Cond(_, tstmt, fstmt) => {
+            collect_assigned_vars(tstmt, result);
+            if let Some(fstmt) = fstmt {
+                collect_assigned_vars(fstmt, result);
+            }
+        },
+        Stmt::While(_, body) => collect_assigned_vars(body, result),
+        _ => (),
+    }
+}
+
 fn verify_decls(decls: &mut VarDecl, fenv: &FEnv, cenv: &CEnv, env: &mut Env, diags: &mut Diags) {
+    let ttype = &decls.type_spec.ttype;
     for var in &mut decls.vars {
-        if let Some(init_exp_node) = &mut var.init {
-            verify_exp(init_exp_node, fenv, cenv, env, diags);
-            let etv = init_exp_node.typeval.as_ref().unwrap();
-            if !etv.has_type(&decls.type_spec.ttype) {
-                diags.push(diag::gen_invalid_expression_type(&decls.type_spec.ttype, &etv, init_exp_node.span));
-            }
-        }
+        let value = match &mut var.init {
+            Some(init_exp_node) => {
+                verify_exp(init_exp_node, fenv, cenv, env, diags);
+                let etv = init_exp_node.typeval.as_ref().unwrap();
+                if !etv.has_type(ttype) {
+                    diags.push(diag::gen_invalid_expression_type(ttype, etv, init_exp_node.span));
+                    ExpTypeVal::from_type(ttype)
+                } else {
+                    etv.clone()
+                }
+            },
+            None => default_typeval(ttype),
+        };
 
         // add variable disregarding init exp type mismatch
-        match env.insert_into_top_scope(var.ident.clone(), decls.type_spec.ttype.clone()) {
-            Some(_) => diags.push(diag::gen_multiple_var_decl(&var.ident, var.span)),
-            None => ()
+        declare_var(&var.ident, ttype, value, var.span, env, diags);
+    }
+}
+
+fn verify_incr_decr(ident: &Ident, diff: i32, span: Span, env: &mut Env, diags: &mut Diags) {
+    match env.get(ident) {
+        None => diags.push(diag::gen_undeclared_variable_in_stmt(ident, span)),
+        Some((vtype, _)) if *vtype != Type::Int => {
+            diags.push(diag::gen_invalid_expression_type(&Type::Int, &ExpTypeVal::from_type(vtype), span));
+        },
+        Some((_, value)) => {
+            let value = match value {
+                ExpTypeVal::Int(Some(v)) => ExpTypeVal::Int(v.checked_add(diff)),
+                _ => ExpTypeVal::Int(None),
+            };
+            assign_var(ident, &value, env);
         }
     }
 }
@@ -275,13 +384,17 @@ fn verify_stmt(stmt_node: &mut StmtNode, fn_type: &Type, fenv: &FEnv, cenv: &CEn
             let mut block_returns = false;
             let mut reachable_stmts = vec![];
             env.push_scope();
+            let mut warned = false;
             for mut stmt_node in stmts.drain(..) {
+                // unreachable statements are checked too, but they are not passed to the backend
                 verify_stmt(&mut *stmt_node, fn_type, fenv, cenv, env, diags);
-                let stmt_returned = stmt_node.will_return.unwrap();
-                reachable_stmts.push(stmt_node);
-                if stmt_returned {
-                    block_returns = true;
-                    break;
+                if block_returns && !warned && !is_empty(&stmt_node) {
+                    diags.push(diag::gen_unreachable_code(stmt_node.span));
+                    warned = true;
+                }
+                if !block_returns {
+                    block_returns = stmt_node.will_return.unwrap();
+                    reachable_stmts.push(stmt_node);
                 }
             }
             env.pop_scope();
@@ -294,39 +407,56 @@ fn verify_stmt(stmt_node: &mut StmtNode, fn_type: &Type, fenv: &FEnv, cenv: &CEn
         },
         Stmt::Ass(MemLoc::Var(ident), exp_node) => {
             verify_exp(exp_node, fenv, cenv, env, diags);
+            let etv = exp_node.typeval.as_ref().unwrap();
             match env.get(ident) {
                 None => {
                     diags.push(diag::gen_undeclared_variable_in_stmt(&ident, stmt_node.span));
                 },
-                Some(var_type) => {
-                    let etv = exp_node.typeval.as_ref().unwrap();
+                Some((var_type, _)) => {
                     if etv.has_valid_type() && !etv.has_type(var_type) {
                         diags.push(diag::gen_invalid_expression_type(var_type, &etv, exp_node.span));
                     }
                 }
             };
+            assign_var(ident, etv, env);
             Some(false)
         },
-        Stmt::Incr(MemLoc::Var(ident)) | Stmt::Decr(MemLoc::Var(ident)) => {
-            match env.get(ident) {
-                None => diags.push(diag::gen_undeclared_variable_in_stmt(ident, stmt_node.span)),
-                Some(vtype) => {
-                    if *vtype != Type::Int {
-                        diags.push(diag::gen_invalid_expression_type(&Type::Int, &ExpTypeVal::from_type(vtype), stmt_node.span));
-                    }
+        Stmt::Incr(MemLoc::Var(ident)) => {
+            verify_incr_decr(ident, 1, stmt_node.span, env, diags);
+            Some(false)
+        },
+        Stmt::Decr(MemLoc::Var(ident)) => {
+            verify_incr_decr(ident, -1, stmt_node.span, env, diags);
+            Some(false)
+        },
+        Stmt::Ass(MemLoc::Field(field), exp_node) => {
+            verify_exp(exp_node, fenv, cenv, env, diags);
+            verify_object_field(field, cenv, env, diags);
+            let etv = exp_node.typeval.as_ref().unwrap();
+            let field_type: Result<Type, ()> = field.typeval.as_ref().unwrap().try_into();
+            // invalid field was already reported by verify_object_field
+            if let Ok(field_type) = field_type {
+                if etv.has_valid_type() && !etv.has_type(&field_type) {
+                    diags.push(diag::gen_invalid_expression_type(&field_type, &etv, exp_node.span));
                 }
-            };
+            }
             Some(false)
         },
-        Stmt::Incr(MemLoc::Field(_)) | Stmt::Decr(MemLoc::Field(_)) | Stmt::Ass(MemLoc::Field(_), _) => {
-            println!("Ignoring field in inc/dec/ass");
+        Stmt::Incr(MemLoc::Field(field)) | Stmt::Decr(MemLoc::Field(field)) => {
+            verify_object_field(field, cenv, env, diags);
+            let ftv = field.typeval.as_ref().unwrap();
+            if ftv.has_valid_type() && !ftv.has_type(&Type::Int) {
+                diags.push(diag::gen_invalid_expression_type(&Type::Int, ftv, stmt_node.span));
+            }
             Some(false)
-        }
+        },
         Stmt::Ret(exp) => {
             verify_exp(exp, fenv, cenv, env, diags);
             let etv = exp.typeval.as_ref().unwrap();
             if !etv.has_type(fn_type) {
                 diags.push(diag::Diagnostic {
+                    code: "E0013",
+                    severity: diag::Severity::Error,
                     message: format!("invalid return type"),
                     details: Some((stmt_node.span, format!("expected {}, found {}", fn_type, &etv)))
                 })
@@ -336,6 +466,8 @@ fn verify_stmt(stmt_node: &mut StmtNode, fn_type: &Type, fenv: &FEnv, cenv: &CEn
         Stmt::VRet => {
             if fn_type != &Type::Void {
                 diags.push(diag::Diagnostic {
+                    code: "E0013",
+                    severity: diag::Severity::Error,
                     message: format!("invalid return type"),
                     details: Some((stmt_node.span, format!("expected {}, found none", fn_type)))
                 })
@@ -345,12 +477,24 @@ fn verify_stmt(stmt_node: &mut StmtNode, fn_type: &Type, fenv: &FEnv, cenv: &CEn
         Stmt::Cond(cond, tstmt, fstmt) => {
             verify_exp(cond, fenv, cenv, env, diags);
 
+            // both branches start with the same values, env holds values after else branch
+            let before = env.clone();
             verify_stmt(tstmt, fn_type, fenv, cenv, env, diags);
+            let after_then = std::mem::replace(env, before);
             if let Some(fstmt) = fstmt {
                 verify_stmt(fstmt, fn_type, fenv, cenv, env, diags);
             }
 
             let ctv = cond.typeval.as_ref().unwrap();
+            let then_returns = tstmt.will_return.unwrap();
+            let else_returns = fstmt.as_ref().map_or(false, |s| s.will_return.unwrap());
+            match ctv {
+                ExpTypeVal::Bool(Some(true)) => *env = after_then,
+                ExpTypeVal::Bool(Some(false)) => (),
+                _ if then_returns => (),
+                _ if else_returns => *env = after_then,
+                _ => merge_values(env, &after_then),
+            }
             match ctv {
                 ExpTypeVal::Bool(condval) => {
                     match (&condval, &fstmt) {
@@ -367,8 +511,13 @@ fn verify_stmt(stmt_node: &mut StmtNode, fn_type: &Type, fenv: &FEnv, cenv: &CEn
             }
         },
         Stmt::While(cond, body) => {
+            // values assigned in the body are unknown in the condition, body and after the loop
+            let mut assigned = Vec::new();
+            collect_assigned_vars(body, &mut assigned);
+            assigned.iter().for_each(|ident| forget_var(ident, env));
             verify_exp(cond, fenv, cenv, env, diags);
             verify_stmt(body, fn_type, fenv, cenv, env, diags);
+            assigned.iter().for_each(|ident| forget_var(ident, env));
 
             let ctv = cond.typeval.as_ref().unwrap();
             match &ctv {
@@ -387,20 +536,81 @@ fn verify_stmt(stmt_node: &mut StmtNode, fn_type: &Type, fenv: &FEnv, cenv: &CEn
         },
         Stmt::EStmt(exp) => {
             verify_exp(exp, fenv, cenv, env, diags);
-            Some(false)
+            match &exp.exp {
+                Exp::Call(ident, _) => Some(DIVERGING_FUNCTIONS.contains(&ident.as_str())),
+                _ => Some(false),
+            }
         },
+        Stmt::Error => Some(false),
     }
 }
 
-pub fn verify_program(prog: &mut Program) -> Diags {
+// ; and {}, and statements skipped after a syntax error, are not worth a warning
+fn is_empty(stmt_node: &StmtNode) -> bool {
+    match &stmt_node.stmt {
+        Stmt::BStmt(stmts) => stmts.is_empty(),
+        Stmt::Error => true,
+        _ => false,
+    }
+}
+
+fn contains_syntax_error(stmt_node: &StmtNode) -> bool {
+    match &stmt_node.stmt {
+        Stmt::Error => true,
+        Stmt::BStmt(stmts) => stmts.iter().any(|s| contains_syntax_error(s)),
+        Stmt::Cond(_, tstmt, fstmt) => contains_syntax_error(tstmt) || fstmt.as_ref().map_or(false, |s| contains_syntax_error(s)),
+        Stmt::While(_, body) => contains_syntax_error(body),
+        _ => false,
+    }
+}
+
+// Finds calls of functions and uses of classes which are not defined.
+struct FindUnknown<'a> {
+    fenv: &'a FEnv,
+    cenv: &'a CEnv,
+    found: bool,
+}
+
+impl<'a> AstMut for FindUnknown<'a> {
+    fn function(&mut self, ident: &mut Ident, _span: Span) {
+        self.found |= !self.fenv.contains_key(ident);
+    }
+
+    fn class(&mut self, ident: &mut Ident, _span: Span) {
+        self.found |= !self.cenv.has_type(ident);
+    }
+}
+
+fn uses_unknown_names(fdef: &mut FnDef, fenv: &FEnv, cenv: &CEnv) -> bool {
+    let mut finder = FindUnknown {fenv, cenv, found: false};
+    finder.type_spec(&mut fdef.type_spec);
+    fdef.params.iter_mut().for_each(|param| finder.decl(param));
+    finder.stmt(&mut fdef.body);
+    finder.found
+}
+
+pub fn builtin_functions() -> Vec<(Ident, FnSignature)> {
+    vec![
+        ("readInt".to_owned(), (Type::Int, vec![])),
+        ("readString".to_owned(), (Type::Str, vec![])),
+        ("printInt".to_owned(), (Type::Void, vec![Type::Int])),
+        ("printString".to_owned(), (Type::Void, vec![Type::Str])),
+        ("error".to_owned(), (Type::Void, vec![])),
+    ]
+}
+
+// A module compiled separately (-c) does not need to define main.
+pub fn verify_program(prog: &mut Program, require_main: bool) -> Diags {
     let mut diags = Vec::new();
 
     // build function env
     let mut fenv = FEnv::new();
-    fenv.insert("readInt".to_owned(), (Type::Int, vec![]));
-    fenv.insert("readString".to_owned(), (Type::Str, vec![]));
-    fenv.insert("printInt".to_owned(), (Type::Void, vec![Type::Int]));
-    fenv.insert("printString".to_owned(), (Type::Void, vec![Type::Str]));
+    for (ident, signature) in builtin_functions() {
+        fenv.insert(ident, signature);
+    }
+    for extern_fn in &prog.externs.functions {
+        fenv.insert(extern_fn.symbol.clone(), extern_fn.signature.clone());
+    }
 
     for fdef in &prog.functions {
         // verify function definitions are unique
@@ -412,28 +622,45 @@ pub fn verify_program(prog: &mut Program) -> Diags {
 
     // verify main exists and has valid signature
     match fenv.get("main") {
+        // main may be a definition skipped by the parser
+        None if !require_main || prog.recovered => (),
         None => diags.push(diag::gen_no_main()),
         Some((Type::Int, args)) if args.is_empty() => (),
         _ => diags.push(diag::gen_invalid_main()),
     }
 
     // process struct definitions
-    let cenv = CEnv::new(&prog.classes);
+    let cenv = CEnv::new(&prog.classes, &prog.externs.classes);
 
 
     // verify each function code
+    let recovered = prog.recovered;
     for fdef in &mut prog.functions {
+        // partially parsed body would produce bogus diagnostics (undeclared variables, missing return...)
+        if contains_syntax_error(&fdef.body) {
+            continue;
+        }
+        // so would uses of skipped definitions, or of definitions of a module which failed to load
+        if recovered && uses_unknown_names(fdef, &fenv, &cenv) {
+            continue;
+        }
         let mut env = Env::new();
-        for decls in &mut fdef.params {
-            verify_decls(decls, &fenv, &cenv, &mut env, &mut diags);
+        for decls in &fdef.params {
+            // values of parameters are not known
+            for var in &decls.vars {
+                let ttype = &decls.type_spec.ttype;
+                declare_var(&var.ident, ttype, ExpTypeVal::from_type(ttype), var.span, &mut env, &mut diags);
+            }
         }
         verify_stmt(&mut fdef.body, &fdef.type_spec.ttype, &fenv, &cenv, &mut env, &mut diags);
 
         if fdef.type_spec.ttype != Type::Void {
             if !fdef.body.will_return.unwrap() {
                 diags.push(diag::Diagnostic {
+                    code: "E0014",
+                    severity: diag::Severity::Error,
                     message: format!("no return statement in non-void function {}", fdef.ident),
-                    details: None
+                    details: Some((Span(fdef.span.1 - 1, fdef.span.1), format!("end of the function is reachable")))
                 });
             }
         }
@@ -0,0 +1,257 @@
+// latte test: golden tests found in a directory. Files under a bad/ directory must be rejected by the
+// compiler, except under bad/runtime/, where they must compile and fail when run. Other files are
+// tests if they have an .output file: they are compiled, run with lli with the .input file (if any)
+// as stdin, and their output must match. Options in the .flags file (if any) are added to the ones
+// latte test got, e.g. --overflow=trap. Runtime errors must be reported by the program ("runtime
+// error" on stderr), and if there is an .stderr file, stderr must match it, other programs must exit
+// successfully. With --bless mismatching .output and .stderr files are overwritten, to add a test
+// create an empty one and run --bless. Tests run in parallel, outputs go to a temporary directory.
+use crate::options::{self, Options};
+use crate::process;
+use crate::source_map::SourceMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Expect {
+    CompileError,
+    RuntimeError,
+    Output,
+}
+
+#[derive(Debug,Clone)]
+pub struct TestCase {
+    pub path: PathBuf,
+    pub expect: Expect,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Outcome {
+    Passed,
+    Blessed, // the output differed and was written to .output
+    Failed(String),
+}
+
+fn has_component(path: &Path, name: &str) -> bool {
+    path.components().any(|component| component == Component::Normal(name.as_ref()))
+}
+
+fn discover_into(dir: &Path, cases: &mut Vec<TestCase>) -> Result<(), io::Error> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            discover_into(&path, cases)?;
+            continue;
+        }
+        if path.extension().map_or(true, |ext| ext != "lat") {
+            continue;
+        }
+        let expect = if has_component(&path, "bad") && has_component(&path, "runtime") {
+            Expect::RuntimeError
+        } else if has_component(&path, "bad") {
+            Expect::CompileError
+        } else if path.with_extension("output").is_file() {
+            Expect::Output
+        } else {
+            continue; // e.g. a module imported by tests
+        };
+        cases.push(TestCase {path, expect});
+    }
+    Ok(())
+}
+
+pub fn discover(dir: &Path) -> Result<Vec<TestCase>, io::Error> {
+    let mut cases = Vec::new();
+    discover_into(dir, &mut cases)?;
+    Ok(cases)
+}
+
+// Lines of both texts, "-" for expected only, "+" for actual only, from their longest common
+// subsequence. Large outputs are only compared up to the first difference.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    if expected.len() * actual.len() > 4_000_000 {
+        let line = expected.iter().zip(&actual).take_while(|(e, a)| e == a).count();
+        return format!("first difference at line {}:\n-{}\n+{}\n", line + 1,
+                       expected.get(line).unwrap_or(&""), actual.get(line).unwrap_or(&""));
+    }
+    // lcs[i][j]: length of the longest common subsequence of expected[i..] and actual[j..]
+    let mut lcs = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut output = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            output += &format!(" {}\n", expected[i]);
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            output += &format!("-{}\n", expected[i]);
+            i += 1;
+        } else {
+            output += &format!("+{}\n", actual[j]);
+            j += 1;
+        }
+    }
+    output
+}
+
+// Compiles the test into out_dir and runs it.
+pub fn run(case: &TestCase, opts: &Options, out_dir: &Path) -> Outcome {
+    let flags_path = case.path.with_extension("flags");
+    let mut opts = match fs::read_to_string(&flags_path) {
+        Err(_) => opts.clone(),
+        Ok(flags) => {
+            let flags: Vec<String> = flags.split_whitespace().map(str::to_owned).collect();
+            match options::parse_args_with(opts.clone(), &flags) {
+                Ok((opts, positional)) if positional.is_empty() => opts,
+                Ok((_, positional)) => return Outcome::Failed(format!("{}: unexpected argument {}", flags_path.display(), positional[0])),
+                Err(msg) => return Outcome::Failed(format!("{}: {}", flags_path.display(), msg)),
+            }
+        },
+    };
+    opts.out_dir = Some(out_dir.to_string_lossy().into_owned());
+    let mut sm = SourceMap::new();
+    let file = match sm.load_file(&case.path.to_string_lossy()) {
+        Ok(file) => file,
+        Err(e) => return Outcome::Failed(format!("cannot read the file: {}", e)),
+    };
+    match (process(&mut sm, file, &case.path, &opts), case.expect) {
+        (Err(_), Expect::CompileError) => return Outcome::Passed,
+        (Ok(_), Expect::CompileError) => return Outcome::Failed("compiled, expected a compile error".to_owned()),
+        (Err(diags), _) => {
+            let messages: Vec<String> = diags.iter()
+                .map(|diag| format!("{} at {}: {}", diag.code, diag.details.as_ref().map_or("-".to_owned(), |(span, _)| sm.format_location(span.0)), diag.message))
+                .collect();
+            return Outcome::Failed(format!("compilation failed:\n{}\n", messages.join("\n")));
+        },
+        (Ok(_), _) => (),
+    }
+
+    let input = case.path.with_extension("input");
+    let stdin = match fs::File::open(&input) {
+        Ok(file) => Stdio::from(file),
+        Err(_) => Stdio::null(),
+    };
+    let result = Command::new("lli").arg(opts.output_path(&case.path, "bc")).stdin(stdin).output();
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => return Outcome::Failed(format!("cannot run lli: {}", e)),
+    };
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if case.expect == Expect::RuntimeError {
+        return check_runtime_error(case, result.status.success(), &stderr, opts.bless);
+    }
+    check_output(case, result.status.success(), &String::from_utf8_lossy(&result.stdout), &stderr, opts.bless)
+}
+
+// A failed program does not pass even if its output matches, e.g. an empty .output when lli
+// rejected the bitcode, and its output is not blessed.
+pub fn check_output(case: &TestCase, success: bool, stdout: &str, stderr: &str, bless: bool) -> Outcome {
+    if !success {
+        return Outcome::Failed(format!("expected a successful run, failed with stderr:\n{}", stderr));
+    }
+    compare(&case.path.with_extension("output"), stdout, bless)
+}
+
+// The program must fail with a runtime error reported by itself, otherwise lli rejecting broken
+// bitcode would pass too. Locations in messages are compared with the file name of the test only,
+// as tests can be run from any directory.
+pub fn check_runtime_error(case: &TestCase, success: bool, stderr: &str, bless: bool) -> Outcome {
+    if success {
+        return Outcome::Failed("ran successfully, expected a runtime error".to_owned());
+    }
+    if !stderr.contains("runtime error") {
+        return Outcome::Failed(format!("expected a runtime error, got:\n{}", stderr));
+    }
+    let stderr_path = case.path.with_extension("stderr");
+    if !stderr_path.is_file() {
+        return Outcome::Passed;
+    }
+    let path = case.path.to_string_lossy();
+    let name = case.path.file_name().map_or(path.clone(), |name| name.to_string_lossy());
+    compare(&stderr_path, &stderr.replace(path.as_ref(), &name), bless)
+}
+
+// Compares with the expected file, which is overwritten with --bless.
+fn compare(expected_path: &Path, actual: &str, bless: bool) -> Outcome {
+    let expected = fs::read_to_string(expected_path).unwrap_or_default();
+    if actual == expected {
+        Outcome::Passed
+    } else if bless {
+        match fs::write(expected_path, actual.as_bytes()) {
+            Ok(_) => Outcome::Blessed,
+            Err(e) => Outcome::Failed(format!("cannot write {}: {}", expected_path.display(), e)),
+        }
+    } else {
+        Outcome::Failed(format!("{} differs:\n{}", expected_path.extension().unwrap_or_default().to_string_lossy(), diff(&expected, actual)))
+    }
+}
+
+// Runs the tests on all cores, reports them as they finish, returns whether all passed.
+pub fn run_all(cases: Vec<TestCase>, opts: &Options) -> bool {
+    let work_dir = std::env::temp_dir().join(format!("latte-test-{}", std::process::id()));
+    let cases = Arc::new(cases);
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(cases.len().max(1));
+    let workers: Vec<_> = (0..threads).map(|_| {
+        let (cases, next, sender, opts) = (cases.clone(), next.clone(), sender.clone(), opts.clone());
+        let work_dir = work_dir.clone();
+        thread::spawn(move || loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let case = match cases.get(i) {
+                Some(case) => case,
+                None => break,
+            };
+            // every test has its own directory, names of test files are not unique
+            let out_dir = work_dir.join(i.to_string());
+            let outcome = match fs::create_dir_all(&out_dir) {
+                Ok(_) => run(case, &opts, &out_dir),
+                Err(e) => Outcome::Failed(format!("cannot create {}: {}", out_dir.display(), e)),
+            };
+            let _ = fs::remove_dir_all(&out_dir);
+            sender.send((i, outcome)).unwrap();
+        })
+    }).collect();
+    drop(sender);
+
+    let mut outcomes: Vec<(usize, Outcome)> = Vec::new();
+    for (i, outcome) in receiver {
+        let status = match &outcome {
+            Outcome::Passed => "ok",
+            Outcome::Blessed => "blessed",
+            Outcome::Failed(_) => "FAILED",
+        };
+        println!("test {} ... {}", cases[i].path.display(), status);
+        outcomes.push((i, outcome));
+    }
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let _ = fs::remove_dir_all(&work_dir);
+
+    outcomes.sort_by_key(|(i, _)| *i);
+    let count = |f: fn(&Outcome) -> bool| outcomes.iter().filter(|(_, outcome)| f(outcome)).count();
+    let failed = count(|outcome| matches!(outcome, Outcome::Failed(_)));
+    for (i, outcome) in &outcomes {
+        if let Outcome::Failed(reason) = outcome {
+            println!("\n---- {} ----\n{}", cases[*i].path.display(), reason.trim_end());
+        }
+    }
+    println!("\ntest result: {}. {} passed; {} failed; {} blessed",
+             if failed == 0 { "ok" } else { "FAILED" },
+             count(|outcome| *outcome == Outcome::Passed), failed, count(|outcome| *outcome == Outcome::Blessed));
+    failed == 0
+}
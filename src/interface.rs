@@ -0,0 +1,133 @@
+// Interface files (.lati) written next to modules compiled separately with -c. They list exported
+// functions and classes with their signatures, so importing files are checked against the interface
+// instead of parsing the imported file again. Classes used by exported definitions are included too,
+// also when they are private or come from other modules, so their fields can be accessed.
+use crate::ast::*;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Interface of the compiled module, prefix is the one its definitions were renamed with.
+pub fn from_program(prog: &Program, prefix: &str) -> Interface {
+    let local_prefix = format!("{}.", prefix);
+    let local = |symbol: &Ident| symbol.strip_prefix(&local_prefix).unwrap_or(symbol).to_owned();
+    let functions: Vec<ExternFn> = prog.functions.iter().filter(|fdef| fdef.exported)
+        .map(|fdef| ExternFn {name: local(&fdef.ident), symbol: fdef.ident.clone(), signature: fdef.get_signature()})
+        .collect();
+
+    let mut pending: Vec<Ident> = prog.classes.iter().filter(|class| class.exported).map(|class| class.ident.clone()).collect();
+    for extern_fn in &functions {
+        let (ret_type, param_types) = &extern_fn.signature;
+        pending.extend(std::iter::once(ret_type).chain(param_types).filter_map(class_of));
+    }
+    let mut classes = Vec::new();
+    let mut seen = HashSet::new();
+    while let Some(symbol) = pending.pop() {
+        if !seen.insert(symbol.clone()) {
+            continue;
+        }
+        let class = if let Some(class) = prog.classes.iter().find(|class| class.ident == symbol) {
+            ExternClass {name: local(&class.ident), symbol, exported: class.exported, fields: class.get_fields()}
+        } else if let Some(class) = prog.externs.classes.iter().find(|class| class.symbol == symbol) {
+            ExternClass {exported: false, ..class.clone()}
+        } else {
+            continue;
+        };
+        pending.extend(class.fields.iter().filter_map(|(_, ttype)| class_of(ttype)));
+        classes.push(class);
+    }
+    classes.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    Interface {functions, classes}
+}
+
+fn class_of(ttype: &Type) -> Option<Ident> {
+    match ttype {
+        Type::Class(ident) => Some(ident.clone()),
+        _ => None,
+    }
+}
+
+// Types are written as in --emit=ast-json, classes by their symbols.
+fn type_to_json(ttype: &Type) -> Value {
+    match ttype {
+        Type::Class(ident) => json!({"class": ident}),
+        _ => json!(ttype.to_string()),
+    }
+}
+
+fn type_from_json(value: &Value) -> Result<Type, String> {
+    match value {
+        Value::String(name) => match name.as_str() {
+            "int" => Ok(Type::Int),
+            "boolean" => Ok(Type::Bool),
+            "string" => Ok(Type::Str),
+            "void" => Ok(Type::Void),
+            _ => Err(format!("unknown type {}", name)),
+        },
+        _ => get_str(value, "class").map(|ident| Type::Class(ident.to_owned())),
+    }
+}
+
+fn get_str<'a>(value: &'a Value, key: &str) -> Result<&'a str, String> {
+    value[key].as_str().ok_or_else(|| format!("missing {}", key))
+}
+
+fn get_array<'a>(value: &'a Value, key: &str) -> Result<&'a Vec<Value>, String> {
+    value[key].as_array().ok_or_else(|| format!("missing {}", key))
+}
+
+pub fn to_json(interface: &Interface) -> Value {
+    let functions: Vec<Value> = interface.functions.iter().map(|extern_fn| {
+        let (ret_type, param_types) = &extern_fn.signature;
+        json!({
+            "name": extern_fn.name,
+            "symbol": extern_fn.symbol,
+            "return": type_to_json(ret_type),
+            "params": param_types.iter().map(type_to_json).collect::<Vec<Value>>(),
+        })
+    }).collect();
+    let classes: Vec<Value> = interface.classes.iter().map(|class| {
+        json!({
+            "name": class.name,
+            "symbol": class.symbol,
+            "exported": class.exported,
+            "fields": class.fields.iter().map(|(ident, ttype)| json!({"name": ident, "type": type_to_json(ttype)})).collect::<Vec<Value>>(),
+        })
+    }).collect();
+    json!({"functions": functions, "classes": classes})
+}
+
+pub fn from_json(value: &Value) -> Result<Interface, String> {
+    let functions = get_array(value, "functions")?.iter().map(|extern_fn| {
+        let param_types = get_array(extern_fn, "params")?.iter().map(type_from_json).collect::<Result<_, _>>()?;
+        Ok(ExternFn {
+            name: get_str(extern_fn, "name")?.to_owned(),
+            symbol: get_str(extern_fn, "symbol")?.to_owned(),
+            signature: (type_from_json(&extern_fn["return"])?, param_types),
+        })
+    }).collect::<Result<_, String>>()?;
+    let classes = get_array(value, "classes")?.iter().map(|class| {
+        let fields = get_array(class, "fields")?.iter()
+            .map(|field| Ok((get_str(field, "name")?.to_owned(), type_from_json(&field["type"])?)))
+            .collect::<Result<_, String>>()?;
+        Ok(ExternClass {
+            name: get_str(class, "name")?.to_owned(),
+            symbol: get_str(class, "symbol")?.to_owned(),
+            exported: class["exported"].as_bool().ok_or("missing exported")?,
+            fields,
+        })
+    }).collect::<Result<_, String>>()?;
+    Ok(Interface {functions, classes})
+}
+
+pub fn write(path: &Path, interface: &Interface) -> Result<(), io::Error> {
+    fs::write(path, format!("{}\n", serde_json::to_string_pretty(&to_json(interface)).unwrap()))
+}
+
+pub fn read(path: &Path) -> Result<Interface, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    from_json(&value)
+}
@@ -0,0 +1,106 @@
+pub mod ast;
+pub mod diag;
+pub mod frontend;
+pub mod backend;
+pub mod utils;
+pub mod scoped_map;
+pub mod options;
+pub mod explain;
+pub mod analysis;
+pub mod formatter;
+pub mod dump;
+pub mod opt;
+pub mod debuginfo;
+pub mod source_map;
+pub mod modules;
+pub mod interface;
+pub mod manifest;
+pub mod project;
+pub mod cache;
+pub mod golden;
+
+#[macro_use] extern crate lalrpop_util;
+lalrpop_mod!(pub latte);
+pub type ParseError<'i> = lalrpop_util::ParseError<usize, latte::Token<'i>, ast::LexicalError>;
+pub type ErrorRecovery<'i> = lalrpop_util::ErrorRecovery<usize, latte::Token<'i>, ast::LexicalError>;
+
+use std::path::Path;
+use source_map::{FileId, SourceMap};
+
+// Parses the source with error recovery, returns the ast if the parser managed to build one.
+pub fn parse(source: &str) -> (Option<ast::Program>, Vec<diag::Diagnostic>) {
+    let stripped = utils::remove_comments(source);
+
+    let mut errors: Vec<ErrorRecovery> = Vec::new();
+    let result = latte::GProgramParser::new().parse(&mut errors, &stripped);
+    let mut diags: Vec<diag::Diagnostic> = errors.into_iter().map(|e| diag::gen_from_parse_error(e.error, &stripped)).collect();
+
+    match result {
+        Err(e) => {
+            diags.push(diag::gen_from_parse_error(e, &stripped));
+            (None, diags)
+        }
+        Ok(ast) => (Some(ast), diags)
+    }
+}
+
+// Parses and verifies the source, returns the ast if the parser managed to build one.
+pub fn check(source: &str) -> (Option<ast::Program>, Vec<diag::Diagnostic>) {
+    let (ast, mut diags) = parse(source);
+    let mut ast = match ast {
+        None => return (None, diags),
+        Some(ast) => ast,
+    };
+    diags.extend(frontend::verify_program(&mut ast, true));
+    (Some(ast), diags)
+}
+
+// Like check, but for the file with all files it imports, which are added to the source map.
+// With -c, imported files are not read, only their interfaces, and main is not required.
+pub fn check_program(sm: &mut SourceMap, file: FileId, opts: &options::Options) -> (Option<ast::Program>, Vec<diag::Diagnostic>) {
+    let (ast, mut diags) = modules::load_program(sm, file, opts);
+    let mut ast = match ast {
+        None => return (None, diags),
+        Some(ast) => ast,
+    };
+    diags.extend(frontend::verify_program(&mut ast, !opts.compile_only));
+    (Some(ast), diags)
+}
+
+fn gen_backend_error(message: String) -> Vec<diag::Diagnostic> {
+    vec![diag::Diagnostic{
+        code: "E0017",
+        severity: diag::Severity::Error,
+        message,
+        details: None
+    }]
+}
+
+// Compiles the file, returns reported warnings or all diagnostics if compilation failed.
+pub fn process(sm: &mut SourceMap, file: FileId, path: &Path, opts: &options::Options) -> Result<Vec<diag::Diagnostic>, Vec<diag::Diagnostic>> {
+    let (ast, mut diags) = check_program(sm, file, opts);
+    if opts.warnings == options::Warnings::Allow {
+        diags.retain(|diag| diag.severity == diag::Severity::Error);
+    }
+    let failed = diags.iter().any(|diag| diag.severity == diag::Severity::Error || opts.warnings == options::Warnings::Deny);
+    let ast = match ast {
+        Some(ast) if !failed => ast,
+        _ => return Err(diags),
+    };
+
+    if let Err(msg) = backend::compile(&ast, sm, path, opts) {
+        return Err(gen_backend_error(msg.to_string()));
+    }
+
+    // the prefix given to the root module by modules::load_program
+    if opts.compile_only {
+        let prefix = path.file_stem().unwrap().to_string_lossy();
+        let interface = interface::from_program(&ast, &prefix);
+        if let Err(e) = interface::write(&opts.output_path(path, "lati"), &interface) {
+            return Err(gen_backend_error(format!("error while writing interface: {}", e)));
+        }
+    }
+
+    return Ok(diags)
+}
+
@@ -1,89 +1,183 @@
-pub mod ast;
-pub mod diag;
-pub mod frontend;
-pub mod backend;
-pub mod utils;
-pub mod scoped_map;
-
-#[macro_use] extern crate lalrpop_util;
-lalrpop_mod!(pub latte);
-pub type ParseError<'i> = lalrpop_util::ParseError<usize, latte::Token<'i>, &'static str>;
-
+use latte::{process, backend, cache, diag, dump, explain, formatter, golden, options, project};
+use latte::source_map::{FileId, SourceMap};
 use std::fs;
-use std::io;
 use std::path::Path;
 use std::panic::PanicInfo;
 
-pub struct File {
-    file_db: codespan::Files, // works only for single file
-    file_id: codespan::FileId, // codespan is broken
+fn panic_hook(info: &PanicInfo) {
+    eprintln!("ERROR\n");
+    eprintln!("internal compiler error :'(");
+    eprintln!("{}", info);
 }
 
-impl File {
-    pub fn new(name: &str) -> Result<File, io::Error> {
-        let content = fs::read_to_string(Path::new(name))?;
-        let mut file_db = codespan::Files::default();
-        let file_id = file_db.add(name, content);
-        Ok(File {file_db, file_id})
-    }
-
-    pub fn get_content(&self) -> &str { self.file_db.source(self.file_id) }
+fn die(msg: &str) -> ! {
+    eprintln!("ERROR\n");
+    eprintln!("{}", msg);
+    std::process::exit(1);
 }
 
-fn process(file: &File, path: &Path) -> Result<(), Vec<diag::Diagnostic>> {
-    let stripped = utils::remove_comments(file.get_content());
+fn print_diags(diags: &[diag::Diagnostic], sm: &SourceMap, opts: &options::Options) {
+    diag::print(diags, sm, opts.error_format);
+}
 
-    let mut ast = match latte::GProgramParser::new().parse(&stripped) {
-        Err(e) => return Err(vec![diag::gen_from_parse_error(e)]),
-        Ok(v) => v
-    };
+// Rewrites given files in canonical format, with --check only reports files that would change.
+fn fmt(paths: &[String], opts: &options::Options) -> ! {
+    let mut success = true;
+    for path in paths {
+        let mut sm = SourceMap::new();
+        let file = sm.load_file(path)
+            .unwrap_or_else(|e| die(&format!("error while reading file {}: {}", path, e)));
+        match formatter::format_source(sm.get_content(file)) {
+            Err(diags) => {
+                print_diags(&diags, &sm, opts);
+                success = false;
+            },
+            Ok(formatted) if formatted == sm.get_content(file) => (),
+            Ok(_) if opts.check => {
+                eprintln!("{}: not formatted", path);
+                success = false;
+            },
+            Ok(formatted) => {
+                fs::write(path, formatted)
+                    .unwrap_or_else(|e| die(&format!("error while writing file {}: {}", path, e)));
+            },
+        }
+    }
+    std::process::exit(if success { 0 } else { 1 });
+}
 
-    let diags = frontend::verify_program(&mut ast);
-    if !diags.is_empty() {
-        return Err(diags);
+// Links files compiled with -c and the runtime into a program, -o a.bc by default.
+fn link(paths: &[String], opts: &options::Options) -> ! {
+    if paths.is_empty() {
+        die("expected files to link");
+    }
+    let inputs: Vec<&Path> = paths.iter().map(Path::new).collect();
+    let output = opts.output.as_deref().unwrap_or("a.bc");
+    match backend::link(&inputs, Path::new(output)) {
+        Err(msg) => die(&msg),
+        Ok(_) => {
+            eprintln!("OK");
+            std::process::exit(0);
+        }
     }
+}
 
-//    println!("{:?}", ast);
+// With --verbose, how many compilations the cache saved.
+fn print_cache_summary(opts: &options::Options) {
+    if let (Some(dir), true) = (&opts.cache_dir, opts.verbose) {
+        eprintln!("{}", cache::Cache::new(dir).summary());
+    }
+}
 
-    if let Err(msg) = backend::compile(&ast, path) {
-        return Err(vec![diag::Diagnostic{
-            message: msg.to_string(),
-            details: None
-        }]);
+// Runs golden tests found in the directories.
+fn test(dirs: &[String], opts: &options::Options) -> ! {
+    if dirs.is_empty() {
+        die("expected directories with tests");
+    }
+    let mut cases = Vec::new();
+    for dir in dirs {
+        cases.extend(golden::discover(Path::new(dir))
+            .unwrap_or_else(|e| die(&format!("error while reading directory {}: {}", dir, e))));
     }
+    std::process::exit(if golden::run_all(cases, opts) { 0 } else { 1 });
+}
 
-    return Ok(())
+// latte build and latte clean, in the project containing the current directory. The manifest gives
+// defaults of options, so build parses the arguments again.
+fn project_command(command: &str, args: &[String], opts: &options::Options) -> ! {
+    let cwd = std::env::current_dir().unwrap_or_else(|e| die(&format!("error while reading current directory: {}", e)));
+    let root = project::find_root(&cwd)
+        .unwrap_or_else(|| die(&format!("could not find {} in the current directory or its parents", project::MANIFEST)));
+    let result = match command {
+        "build" => project::build(&root, args).map(|_| ()),
+        _ => project::clean(&root),
+    };
+    print_cache_summary(opts);
+    match result {
+        Err(msg) => die(&msg),
+        Ok(_) => std::process::exit(0),
+    }
 }
 
-fn panic_hook(info: &PanicInfo) {
-    eprintln!("ERROR\n");
-    eprintln!("internal compiler error :'(");
-    eprintln!("{}", info);
+// Prints the ast instead of compiling, the typed ast is printed even if the frontend reported errors.
+// The typed ast includes imported files.
+fn emit(sm: &mut SourceMap, file: FileId, emit: options::Emit, opts: &options::Options) -> ! {
+    use options::Emit;
+    let (ast, diags) = match emit {
+        Emit::Ast | Emit::AstJson => latte::parse(sm.get_content(file)),
+        Emit::TypedAst | Emit::TypedAstJson => latte::check_program(sm, file, opts),
+    };
+    if let Some(ast) = &ast {
+        match emit {
+            Emit::Ast => print!("{}", dump::tree(ast, false)),
+            Emit::TypedAst => print!("{}", dump::tree(ast, true)),
+            Emit::AstJson => println!("{}", dump::json(ast, false)),
+            Emit::TypedAstJson => println!("{}", dump::json(ast, true)),
+        }
+    }
+    if !diags.is_empty() {
+        print_diags(&diags, sm, opts);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
 }
 
 fn main() {
 //    std::panic::set_hook(Box::new(panic_hook));
-    fn die(msg: &str) -> ! {
-        eprintln!("ERROR\n");
-        eprintln!("{}", msg);
-        std::process::exit(1);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (opts, inputs) = options::parse_args(&args).unwrap_or_else(|msg| die(&msg));
+
+    if let Some(code) = &opts.explain {
+        match explain::get(code) {
+            Some(ec) => {
+                println!("{}: {}", ec.code, ec.title);
+                print!("{}", ec.explanation);
+                std::process::exit(0);
+            }
+            None => die(&format!("unknown error code: {}", code)),
+        }
     }
-    let args: Vec<String> = std::env::args().collect();
 
-    let path = args.get(1)
-        .unwrap_or_else(|| die(&format!("expected 1 argument, got {}", args.len()-1)));
+    if inputs.first().map(String::as_str) == Some("fmt") {
+        fmt(&inputs[1..], &opts);
+    }
+    if inputs.first().map(String::as_str) == Some("link") {
+        link(&inputs[1..], &opts);
+    }
+    if inputs.first().map(String::as_str) == Some("test") {
+        test(&inputs[1..], &opts);
+    }
+    if let Some(command @ "build") | Some(command @ "clean") = inputs.first().map(String::as_str) {
+        if inputs.len() != 1 {
+            die(&format!("latte {} takes no arguments", command));
+        }
+        project_command(command, &args, &opts);
+    }
+
+    if inputs.len() != 1 {
+        die(&format!("expected 1 argument, got {}", inputs.len()));
+    }
+    let path = &inputs[0];
 
-    let file = File::new(path)
+    let mut sm = SourceMap::new();
+    let file = sm.load_file(path)
         .unwrap_or_else(|e| die(&format!("error while reading file {}: {}", path, e)));
 
-    match process(&file, &Path::new(path)) {
+    if let Some(kind) = opts.emit {
+        emit(&mut sm, file, kind, &opts);
+    }
+
+    match process(&mut sm, file, &Path::new(path), &opts) {
         Err(diags) => {
+            // the first line of stderr is ERROR or OK, diagnostics follow it, or go to stdout with --error-format=json
             eprintln!("ERROR\n");
-            diag::print_all(&diags, &file);
+            print_diags(&diags, &sm, &opts);
             std::process::exit(1);
         },
-        Ok(_) => {
+        Ok(warnings) => {
             eprintln!("OK");
+            print_diags(&warnings, &sm, &opts);
+            print_cache_summary(&opts);
             std::process::exit(0);
         }
     }
@@ -91,22 +185,53 @@ fn main() {
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use latte::ast::Type;
+    use latte::interface;
+    use latte::manifest::{Manifest, Profile};
+
     fn test_case(path: &str, expect_success: bool) -> bool {
         eprint!("{} => ", path);
         let success: bool;
-        let file = File::new(path).unwrap();
-        let result = process(&file, &Path::new(path));
+        let mut sm = SourceMap::new();
+        let file = sm.load_file(path).unwrap();
+        let result = process(&mut sm, file, &Path::new(path), &options::Options::default());
         match result {
             Err(_) => success = !expect_success,
             Ok(_) => success = expect_success,
         }
         eprintln!("{}", if success { "OK" } else { "ERR" });
         if let Err(_) = result {
-//        diag::print_all(&diags, &file);
+//        diag::print_all(&diags, &sm);
         }
         return success;
     }
 
+    // Compiles a copy of the file in a temporary directory, returns the generated IR.
+    fn compile_to_ir(path: &str, opts: &options::Options) -> String {
+        let path = Path::new(path);
+        let dir = std::env::temp_dir().join(format!("latte_ir_{}_{}", path.file_stem().unwrap().to_string_lossy(), std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let copy = dir.join(path.file_name().unwrap());
+        fs::copy(path, &copy).unwrap();
+        let mut sm = SourceMap::new();
+        let file = sm.load_file(copy.to_str().unwrap()).unwrap();
+        assert!(process(&mut sm, file, &copy, opts).is_ok(), "cannot compile {}", path.display());
+        let ir = fs::read_to_string(copy.with_extension("ll")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        ir
+    }
+
+    // Definition of the function in the IR, from define to the closing brace.
+    fn function_ir<'a>(ir: &'a str, name: &str) -> &'a str {
+        let header = format!(" @{}(", name);
+        let start = ir.match_indices("define ").map(|(i, _)| i)
+            .find(|&i| ir[i..].lines().next().unwrap().contains(&header))
+            .unwrap_or_else(|| panic!("no definition of {}", name));
+        let end = start + ir[start..].find("\n}\n").unwrap();
+        &ir[start..end]
+    }
+
     #[test]
     fn good() {
         let mut success = true;
@@ -127,4 +252,720 @@ mod test {
         }
         assert!(success);
     }
+
+    // expected error code for every file in lattests/bad
+    const BAD_CODES: &[(&str, &str)] = &[
+        ("bad001", "E0015"), ("bad002", "E0015"), ("bad003", "E0004"), ("bad004", "E0015"),
+        ("bad005", "E0015"), ("bad006", "E0005"), ("bad007", "E0004"), ("bad008", "E0014"),
+        ("bad009", "E0006"), ("bad010", "E0013"), ("bad011", "E0013"), ("bad012", "E0014"),
+        ("bad013", "E0010"), ("bad015", "E0012"), ("bad016", "E0012"), ("bad017", "E0012"),
+        ("bad018", "E0012"), ("bad019", "E0012"), ("bad020", "E0010"), ("bad021", "E0014"),
+        ("bad022", "E0006"), ("bad023", "E0006"), ("bad024", "E0014"), ("bad025", "E0014"),
+        ("bad026", "E0006"), ("bad027", "E0006"),
+    ];
+
+    #[test]
+    fn bad_codes() {
+        for entry in fs::read_dir("./lattests/bad").unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
+            let expected = BAD_CODES.iter().find(|(n, _)| *n == name)
+                .unwrap_or_else(|| panic!("no expected error code for {}", path.display())).1;
+
+            let mut sm = SourceMap::new();
+            let file = sm.load_file(path.to_str().unwrap()).unwrap();
+            let codes: Vec<&str> = match process(&mut sm, file, &path, &options::Options::default()) {
+                Err(diags) => diags.iter().map(|d| d.code).collect(),
+                Ok(_) => vec![],
+            };
+            assert!(codes.contains(&expected), "{}: expected {}, found {:?}", path.display(), expected, codes);
+            assert!(explain::get(expected).is_some(), "{} has no explanation", expected);
+        }
+    }
+
+    #[test]
+    fn fmt_is_idempotent() {
+        let mut paths = vec!["./lattests/comments.lat".to_owned()];
+        for dir in &["./lattests/good", "./lattests/other"] {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension().map_or(false, |e| e == "lat") {
+                    paths.push(path.to_str().unwrap().to_owned());
+                }
+            }
+        }
+        for path in paths {
+            let source = fs::read_to_string(&path).unwrap();
+            let once = formatter::format_source(&source).unwrap_or_else(|_| panic!("cannot format {}", path));
+            let twice = formatter::format_source(&once).unwrap();
+            assert_eq!(once, twice, "{}: formatting is not idempotent", path);
+            let (_, diags) = latte::check(&once);
+            let (_, expected) = latte::check(&source);
+            assert_eq!(diags.len(), expected.len(), "{}: formatting changed the meaning", path);
+        }
+    }
+
+    #[test]
+    fn fmt_output() {
+        let source = "// header\nint main(){int x=-(1+2)*3,y;if(x<0||(x>1&&true))x--;else if (!(x==1)) {y=- -x; } // tail\n\n\n  printString(\"a\\\\b\\\"c\\n\");while(false);return (x-(y-1))-2;}";
+        let expected = "\
+// header
+int main() {
+    int x = -(1 + 2) * 3, y;
+    if (x < 0 || x > 1 && true) {
+        x--;
+    } else if (!(x == 1)) {
+        y = - -x;
+    } // tail
+
+    printString(\"a\\\\b\\\"c\\n\");
+    while (false) {
+    }
+    return x - (y - 1) - 2;
+}
+";
+        assert_eq!(formatter::format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn constant_propagation() {
+        let returns = |body: &str| {
+            let (_, diags) = latte::check(&format!("int main() {{\n{}\n}}\n", body));
+            let codes: Vec<&str> = diags.iter().map(|d| d.code).collect();
+            assert!(codes.iter().all(|c| *c == "E0014"), "{}: unexpected {:?}", body, codes);
+            codes.is_empty()
+        };
+        assert!(returns("int x = 5; if (x > 3) return 1;"));
+        assert!(returns("int x; x++; if (x == 1) return 1;"));
+        assert!(returns("boolean b; if (!b) return 1;"));
+        assert!(returns("int x = 1; if (readInt() > 0) x = 2; else x = 2; if (x == 2) return 1;"));
+        assert!(returns("int x = 1; if (readInt() > 0) { x = 2; return 0; } if (x == 1) return 1;"));
+        assert!(returns("int x = 1; { int x = 2; x++; } if (x == 1) return 1;"));
+        assert!(!returns("int x = 1; if (readInt() > 0) x = 2; if (x == 1) return 1;"));
+        assert!(!returns("int x = 1; while (readInt() > 0) x++; if (x == 1) return 1;"));
+        assert!(!returns("int x = 1; while (x < 10) x++; if (x == 1) return 1;"));
+        assert!(!returns("int x = readInt(); if (x == 1) return 1;"));
+        assert!(returns("int x = 1; while (x > 0) printInt(x);"));
+        // overflow and division by zero are left to the runtime
+        assert!(returns("if (2147483646 + 1 > 0) return 1;"));
+        assert!(!returns("if (2147483647 + 1 < 0) return 1;"));
+        assert!(!returns("int x = 2147483647; x++; if (x < 0) return 1;"));
+        assert!(!returns("if (1 / 0 == 0) return 1;"));
+        assert!(!returns("int x = -2147483647 - 1; if (x / -1 < 0) return 1;"));
+    }
+
+    #[test]
+    fn return_reachability() {
+        let codes = |body: &str| {
+            let (_, diags) = latte::check(&format!("int main() {{\n{}\n}}\n", body));
+            diags.iter().map(|d| d.code).collect::<Vec<&str>>()
+        };
+        assert!(codes("while (true) {}").is_empty());
+        assert!(codes("while (true) { if (readInt() > 0) return 1; }").is_empty());
+        assert!(codes("if (true) return 1;").is_empty());
+        assert!(codes("error();").is_empty());
+        assert!(codes("if (readInt() > 0) return 1; else error();").is_empty());
+        assert_eq!(codes("while (readInt() > 0) return 1;"), vec!["E0014"]);
+        assert_eq!(codes("if (readInt() > 0) error();"), vec!["E0014"]);
+        // unreachable code is still checked
+        assert_eq!(codes("return 0; x = 1;"), vec!["E0005", "W0001"]);
+
+        let (_, diags) = latte::check("int main() {\n  printInt(1);\n}\n");
+        let span = diags[0].details.as_ref().unwrap().0;
+        assert_eq!((span.0, span.1), (28, 29));
+
+        // listed as fixed in KNOWN_PROBLEMS.md, also run by golden_run
+        let file_codes = |path: &str| latte::check(&fs::read_to_string(path).unwrap()).1.iter().map(|d| d.code).collect::<Vec<&str>>();
+        assert!(file_codes("./lattests/students/good/basic/while_true.lat").is_empty());
+        // printInt(1) after if (true) return;
+        assert_eq!(file_codes("./lattests/students/good/basic/void_return.lat"), vec!["W0001"]);
+    }
+
+    #[test]
+    fn syntax_error_messages() {
+        let check = |source: &str| {
+            let (_, diags) = latte::check(source);
+            assert_eq!(diags.len(), 1, "{}", source);
+            let (span, comment) = diags[0].details.clone().unwrap();
+            (diags[0].message.clone(), source[span.0..span.1].to_owned(), comment)
+        };
+        let missing = ("missing `;` after statement".to_owned(), "1".to_owned(), "expected `;` after this".to_owned());
+        assert_eq!(check("int main() { int x = 1 return x; }"), missing);
+        assert_eq!(check("int main() {\n  int x = 1\n  return x;\n}\n"), missing);
+        // not a statement keyword, the expected tokens are listed
+        let (message, token, comment) = check("int main() { int x = 1 x; }");
+        assert_eq!((message.as_str(), token.as_str()), ("syntax error", "x"));
+        assert!(comment.starts_with("unexpected `x`, expected one of: "), "{}", comment);
+
+        // terminals are named, not given as regexes, and listed once
+        for source in &["int main() { printInt(1 +); }", "int main() { printInt(1 2); }", "int main() { int 1; }", "int main() { return (x; }"] {
+            let (_, _, comment) = check(source);
+            assert!(!comment.contains("r#") && !comment.contains('[') && !comment.contains('\\'), "{}", comment);
+            let names: Vec<&str> = comment.split(": ").nth(1).unwrap_or("").split(", ").collect();
+            assert!(names.iter().enumerate().all(|(i, name)| !names[..i].contains(name)), "{}", comment);
+        }
+        let (_, _, comment) = check("int main() { printInt(1 +); }");
+        assert!(comment.contains("integer literal") && comment.contains("identifier") && comment.contains("string literal"), "{}", comment);
+    }
+
+    #[test]
+    fn integer_literal_range() {
+        let check = |literal: &str| {
+            let source = format!("int main() {{\n  printInt({});\n  return 0;\n}}\n", literal);
+            latte::check(&source).1.iter()
+                .map(|d| { let span = d.details.as_ref().unwrap().0; (d.code, d.message.clone(), source[span.0..span.1].to_owned()) })
+                .collect::<Vec<(&str, String, String)>>()
+        };
+        assert_eq!(check("2147483648"), vec![("E0016", "integer literal 2147483648 out of range for int".to_owned(), "2147483648".to_owned())]);
+        assert_eq!(check("-2147483649"), vec![("E0016", "integer literal -2147483649 out of range for int".to_owned(), "-2147483649".to_owned())]);
+        assert!(check("-2147483648").is_empty());
+        assert!(check("2147483647").is_empty());
+    }
+
+    #[test]
+    fn syntax_error_recovery() {
+        let source = "\
+int f() {
+  int x = 1
+  return x;
+}
+int g( {
+  return 1;
+}
+void p() {
+  printInt(f() +);
+}
+int main() {
+  printInt(g());
+  return 0;
+}
+boolean h() {
+  return 1;
+}
+";
+        let (_, diags) = latte::check(source);
+        let found: Vec<(&str, &str)> = diags.iter()
+            .map(|d| { let span = d.details.as_ref().unwrap().0; (d.code, &source[span.0..span.1]) })
+            .collect();
+        // every typo is reported, and definitions which parsed are still type checked, except
+        // main, which uses the skipped g
+        assert_eq!(found, vec![("E0015", "1"), ("E0015", "{"), ("E0015", ")"), ("E0013", "return 1;")]);
+    }
+
+    #[test]
+    fn field_assignments() {
+        let codes = |body: &str| {
+            let (_, diags) = latte::check(&format!("class list {{\n  int elem;\n  list next;\n}}\nint main() {{\n  list l = new list;\n{}\n  return 0;\n}}\n", body));
+            diags.iter().map(|d| d.code).collect::<Vec<&str>>()
+        };
+        assert!(codes("l.elem = 1; l.elem++; l.next = new list; l.next.elem--; l.next = (list)null;").is_empty());
+        assert_eq!(codes("l.elem = true;"), vec!["E0006"]);
+        assert_eq!(codes("l.next = 1;"), vec!["E0006"]);
+        assert_eq!(codes("l.next++;"), vec!["E0006"]);
+        assert_eq!(codes("l.size = 1;"), vec!["E0007"]);
+
+        let source = fs::read_to_string("./lattests/extensions/struct/list.lat").unwrap();
+        assert!(latte::check(&source).1.is_empty());
+    }
+
+    #[test]
+    fn emit_typed_ast() {
+        let (ast, diags) = latte::check("int main() {\n  int x = 2 * 3;\n  if (x > 1) printInt(x);\n  return 0;\n}\n");
+        assert!(diags.is_empty());
+        let expected = "\
+Program
+  FnDef int main
+    Block will_return=true
+      Decl will_return=false
+        Var int x
+          Binary * : int = 6
+            Int 2 : int = 2
+            Int 3 : int = 3
+      Cond will_return=false
+        Binary > : boolean = true
+          Obj x : int = 6
+          Int 1 : int = 1
+        Block will_return=false
+          EStmt will_return=false
+            Call printInt : void
+              Obj x : int = 6
+      Ret will_return=true
+        Int 0 : int = 0
+";
+        let ast = ast.unwrap();
+        assert_eq!(dump::tree(&ast, true), expected);
+        let json = dump::json(&ast, true);
+        assert_eq!(json["functions"][0]["body"]["stmts"][0]["decl"]["vars"][0]["init"]["typeval"]["value"], 6);
+        assert_eq!(json["functions"][0]["body"]["will_return"], true);
+    }
+
+    #[test]
+    fn source_map_locations() {
+        use latte::source_map::Location;
+        let mut sm = SourceMap::new();
+        let a = sm.add_file("a.lat", "int main() {\n  return 0;\n}\n".to_owned());
+        let b = sm.add_file("b.lat", "// zażółć\nvoid f() {}\n".to_owned());
+        assert_eq!(sm.get_location(15), Location {file: a, line: 2, column: 3});
+        let start = sm.get_start(b);
+        assert!(start > sm.get_content(a).len());
+        assert_eq!(sm.lookup_file(start), b);
+        // columns count characters, not bytes
+        let f = start + sm.get_content(b).find('f').unwrap();
+        assert_eq!(sm.get_location(f), Location {file: b, line: 2, column: 6});
+        assert_eq!(sm.format_location(f), "b.lat:2:6");
+
+        for offset in (0..=sm.get_content(a).len()).chain(start..=start + sm.get_content(b).len()) {
+            let location = sm.get_location(offset);
+            if sm.get_content(location.file).is_char_boundary(offset - sm.get_start(location.file)) {
+                assert_eq!(sm.get_offset(location), Some(offset));
+            }
+        }
+        assert_eq!(sm.get_offset(Location {file: a, line: 9, column: 1}), None);
+        assert_eq!(sm.get_offset(Location {file: a, line: 1, column: 20}), None);
+    }
+
+    #[test]
+    fn ssa_phis() {
+        let path = std::env::temp_dir().join(format!("latte_ssa_{}.lat", std::process::id()));
+        fs::write(&path, "\
+int main() {
+  int k = readInt();
+  int i = 0, s = 0;
+  while (i < 10) {
+    s = s + k;
+    i++;
+  }
+  printInt(s);
+  return 0;
+}
+").unwrap();
+        // without src/opt, so the phis are the ones built with the code
+        let opts = options::Options {optimize: false, ..options::Options::default()};
+        let ir = compile_to_ir(path.to_str().unwrap(), &opts);
+        fs::remove_file(&path).unwrap();
+        let body = function_ir(&ir, "main");
+        let header = &body[body.find("\nloop_cond:").unwrap_or_else(|| panic!("no loop header:\n{}", body))..];
+        let header = &header[..header[1..].find("\n\n").map_or(header.len(), |end| end + 1)];
+        let phis: Vec<&str> = header.lines().filter(|line| line.contains(" = phi ")).collect();
+        // i and s change in the loop, k does not, its phi merges only %k and is removed
+        assert_eq!(phis.len(), 2, "{}", header);
+        assert!(phis.iter().all(|phi| !phi.contains("%k,")), "{}", header);
+    }
+
+    #[test]
+    fn debug_info() {
+        let path = std::env::temp_dir().join(format!("latte_debug_{}.lat", std::process::id()));
+        fs::write(&path, "\
+int sq(int a) {
+  int b = a * a;
+  return b;
+}
+
+int main() {
+  int x = readInt();
+  printInt(sq(x));
+  return 0;
+}
+").unwrap();
+        // the IR written with -g, as printed by --dump-ir
+        let opts = options::Options {debug_info: true, ..options::Options::default()};
+        let ir = compile_to_ir(path.to_str().unwrap(), &opts);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(ir.matches("!DICompileUnit(").count(), 1, "{}", ir);
+        for name in &["sq", "main"] {
+            assert!(ir.contains(&format!("!DISubprogram(name: \"{}\"", name)), "{}", ir);
+            let define = ir.lines().find(|line| line.starts_with("define") && line.contains(&format!("@{}(", name))).unwrap();
+            assert!(define.contains("!dbg !"), "{}", define);
+        }
+        assert!(ir.contains("!DILocation(line: 7,") && ir.contains("!DILocation(line: 8,"), "{}", ir);
+        // parameters and locals live in stack slots described by llvm.dbg.declare
+        assert!(ir.contains("call void @llvm.dbg.declare("), "{}", ir);
+        assert!(ir.contains("!DILocalVariable(name: \"a\", arg: 1,"), "{}", ir);
+        assert!(ir.contains("!DILocalVariable(name: \"b\",") && ir.contains("!DILocalVariable(name: \"x\","), "{}", ir);
+        // sq is inlined into main, its code keeps line 2, called from line 8
+        assert!(function_ir(&ir, "main").contains("mul i32"), "{}", ir);
+        assert!(ir.lines().any(|line| line.contains("!DILocation(line: 2,") && line.contains("inlinedAt: !")), "{}", ir);
+    }
+
+    #[test]
+    fn tail_calls() {
+        let ir = compile_to_ir("./lattests/extensions/calls/tail_calls.lat", &options::Options::default());
+        for name in &["count", "swap", "gcd"] {
+            // the only mention of the function in its body is the definition, the tail call is a jump
+            let body = function_ir(&ir, name);
+            assert_eq!(body.matches(&format!("@{}(", name)).count(), 1, "{}", body);
+            assert!(body.contains("tail_call_loop"), "{}", body);
+            // the copy inlined into main calls the function
+            assert!(function_ir(&ir, "main").contains(&format!("call i32 @{}(", name)));
+        }
+    }
+
+    #[test]
+    fn inlining() {
+        let path = "./lattests/extensions/calls/inlining.lat";
+        let ir = compile_to_ir(path, &options::Options::default());
+        let main = function_ir(&ir, "main");
+        // odd is inlined into even, but even is not inlined into the inlined odd
+        assert!(main.contains("call i32 @even(") && !main.contains("call i32 @odd("), "{}", main);
+        let even = function_ir(&ir, "even");
+        assert!(even.contains("call i32 @even(") && !even.contains("call i32 @odd("), "{}", even);
+        // level1 to level4 are inlined, level5 would be the fifth nested one
+        assert!((1..=4).all(|i| !main.contains(&format!("call i32 @level{}(", i))), "{}", main);
+        assert!(main.contains("call i32 @level5("), "{}", main);
+        // fail ends with error(), so nothing jumps to the end of its inlined body
+        assert!(!main.contains("call void @fail("), "{}", main);
+
+        let ir = compile_to_ir(path, &options::Options {inline_limit: 0, ..options::Options::default()});
+        let main = function_ir(&ir, "main");
+        for call in &["call i32 @even(", "call i32 @level1(", "call void @fail("] {
+            assert!(main.contains(call), "{}", main);
+        }
+    }
+
+    // Runs the optimizations of src/opt on the IR, returns their statistics and the optimized IR.
+    fn optimize_ir(ir: &str) -> (latte::opt::Stats, String) {
+        use inkwell::context::Context;
+        use inkwell::memory_buffer::MemoryBuffer;
+        let llvm = Context::create();
+        let md = llvm.create_module_from_ir(MemoryBuffer::create_from_memory_range_copy(ir.as_bytes(), "test")).unwrap();
+        let stats = latte::opt::optimize_module(&llvm, &md);
+        let optimized = md.print_to_string().to_string();
+        assert!(md.verify().is_ok(), "{}", optimized);
+        (stats, optimized)
+    }
+
+    #[test]
+    fn loop_optimizations() {
+        // a * b does not change in the loop, a / b could trap, so it stays
+        let (stats, ir) = optimize_ir("
+define i32 @invariant(i32 %a, i32 %b, i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i.next, %loop ]
+  %sum = phi i32 [ 0, %entry ], [ %sum.next, %loop ]
+  %ab = mul i32 %a, %b
+  %q = sdiv i32 %a, %b
+  %t = add i32 %ab, %q
+  %sum.next = add i32 %sum, %t
+  %i.next = add i32 %i, 1
+  %cond = icmp slt i32 %i.next, %n
+  br i1 %cond, label %loop, label %exit
+
+exit:
+  ret i32 %sum.next
+}
+");
+        assert_eq!((stats.hoisted, stats.reduced), (1, 0), "{}", ir);
+        let loop_start = ir.find("\nloop:").unwrap();
+        assert!(ir.find("%ab = mul").unwrap() < loop_start, "{}", ir);
+        assert!(ir.find("%q = sdiv").unwrap() > loop_start, "{}", ir);
+
+        // i * k for i = 0, 1, 2... becomes a new induction variable increased by k
+        let (stats, ir) = optimize_ir("
+define i32 @induction(i32 %k, i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i.next, %loop ]
+  %sum = phi i32 [ 0, %entry ], [ %sum.next, %loop ]
+  %ik = mul i32 %i, %k
+  %sum.next = add i32 %sum, %ik
+  %i.next = add i32 %i, 1
+  %cond = icmp slt i32 %i.next, %n
+  br i1 %cond, label %loop, label %exit
+
+exit:
+  ret i32 %sum.next
+}
+");
+        assert_eq!((stats.hoisted, stats.reduced), (0, 1), "{}", ir);
+        assert!(!ir.contains("%ik = mul") && ir.contains("%iv = phi"), "{}", ir);
+    }
+
+    #[test]
+    fn common_subexpressions() {
+        let (stats, ir) = optimize_ir("
+declare i8* @__latc_concat_str(i8*, i8*)
+declare void @printInt(i32)
+
+define i32 @impure(i32 %x) {
+entry:
+  call void @printInt(i32 %x)
+  ret i32 %x
+}
+
+define i32 @square(i32 %x) {
+entry:
+  %y = mul i32 %x, %x
+  ret i32 %y
+}
+
+define i64 @cse(i32 %a, i32 %b, i1 %c, i8* %s) {
+entry:
+  %x = add i32 %a, %b
+  %y = add i32 %a, %b
+  %p1 = call i32 @square(i32 %a)
+  %p2 = call i32 @square(i32 %a)
+  %i1 = call i32 @impure(i32 %a)
+  %i2 = call i32 @impure(i32 %a)
+  %s1 = call i8* @__latc_concat_str(i8* %s, i8* %s)
+  %s2 = call i8* @__latc_concat_str(i8* %s, i8* %s)
+  %z32 = zext i1 %c to i32
+  %z64 = zext i1 %c to i64
+  br i1 %c, label %then, label %end
+
+then:
+  %w = add i32 %a, %b
+  br label %end
+
+end:
+  ret i64 %z64
+}
+");
+        assert_eq!((stats.cse.local, stats.cse.global), (2, 1), "{}", ir);
+        let body = function_ir(&ir, "cse");
+        assert!(!body.contains("%y = ") && !body.contains("%p2 = ") && !body.contains("%w = "), "{}", body);
+        assert_eq!(body.matches("call i32 @impure(").count(), 2, "{}", body);
+        assert_eq!(body.matches("@__latc_concat_str(").count(), 2, "{}", body);
+        assert!(body.contains("zext i1 %c to i32") && body.contains("zext i1 %c to i64"), "{}", body);
+    }
+
+    #[test]
+    fn modules() {
+        let check = |path: &str| {
+            let mut sm = SourceMap::new();
+            let file = sm.load_file(path).unwrap();
+            let (ast, diags) = latte::check_program(&mut sm, file, &options::Options::default());
+            (ast, diags.iter().map(|d| d.code).collect::<Vec<&str>>(), sm.len())
+        };
+        let (ast, codes, files) = check("./lattests/extensions/modules/main.lat");
+        assert!(codes.is_empty(), "{:?}", codes);
+        // list.lat is imported twice, but loaded once
+        assert_eq!(files, 3);
+        let mut names: Vec<String> = ast.unwrap().functions.into_iter().map(|f| f.ident).collect();
+        names.sort();
+        assert_eq!(names, vec!["list.cons", "list.fromTo", "list.sum", "list.sumFrom", "main", "strings.repeat", "strings.sumFrom", "sumFrom"]);
+
+        assert_eq!(check("./lattests/extensions/modules/bad/cycle_a.lat").1, vec!["E0019"]);
+        assert_eq!(check("./lattests/extensions/modules/bad/missing.lat").1, vec!["E0018"]);
+        assert_eq!(check("./lattests/extensions/modules/bad/conflict.lat").1, vec!["E0020"]);
+        assert_eq!(check("./lattests/extensions/modules/bad/private.lat").1, vec!["E0021"]);
+        // import and export are keywords
+        assert_eq!(latte::check("int main() {\n  int import = 1;\n  return 0;\n}\n").1[0].code, "E0015");
+
+        let source = "import \"list.lat\";\nimport \"b.lat\";\nexport int f() {\n  return 0;\n}\n";
+        assert_eq!(formatter::format_source(source).unwrap(), "import \"list.lat\";\nimport \"b.lat\";\n\nexport int f() {\n    return 0;\n}\n");
+    }
+
+    #[test]
+    fn separate_compilation() {
+        // copies of the modules, so their interfaces are not written into lattests
+        let dir = std::env::temp_dir().join(format!("latte_separate_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["list.lat", "strings.lat", "main.lat"] {
+            fs::copy(Path::new("./lattests/extensions/modules").join(name), dir.join(name)).unwrap();
+        }
+        let opts = options::Options {compile_only: true, ..options::Options::default()};
+        let compile = |name: &str| {
+            let path = dir.join(name);
+            let mut sm = SourceMap::new();
+            let file = sm.load_file(path.to_str().unwrap()).unwrap();
+            let (ast, diags) = latte::check_program(&mut sm, file, &opts);
+            let codes: Vec<&str> = diags.iter().map(|d| d.code).collect();
+            if let (Some(ast), true) = (&ast, codes.is_empty()) {
+                let prefix = path.file_stem().unwrap().to_str().unwrap();
+                interface::write(&opts.output_path(&path, "lati"), &interface::from_program(ast, prefix)).unwrap();
+            }
+            (ast, codes)
+        };
+
+        // imported modules must be compiled first
+        assert_eq!(compile("main.lat").1, vec!["E0022", "E0022"]);
+        let (list, codes) = compile("list.lat");
+        assert!(codes.is_empty(), "{:?}", codes);
+        // list.lat has no main, but that is not an error in a separately compiled file
+        assert!(list.unwrap().functions.iter().all(|f| f.ident.starts_with("list.")));
+        let list_interface = interface::read(&dir.join("list.lati")).unwrap();
+        let names: Vec<&str> = list_interface.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["cons", "fromTo", "sum"]);
+        assert_eq!(list_interface.classes[0].symbol, "list.list");
+        assert_eq!(list_interface.classes[0].fields, vec![("elem".to_owned(), Type::Int), ("next".to_owned(), Type::Class("list.list".to_owned()))]);
+
+        let (strings, codes) = compile("strings.lat");
+        assert!(codes.is_empty(), "{:?}", codes);
+        // only the file itself is compiled, list.lat is known from its interface
+        let strings = strings.unwrap();
+        assert_eq!(strings.functions.len(), 2);
+        assert_eq!(strings.externs.functions.len(), 3);
+
+        let (main, codes) = compile("main.lat");
+        assert!(codes.is_empty(), "{:?}", codes);
+        let mut names: Vec<String> = main.unwrap().functions.into_iter().map(|f| f.ident).collect();
+        names.sort();
+        assert_eq!(names, vec!["main", "main.sumFrom"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn manifest() {
+        let manifest = Manifest::parse("[project]\nname = \"a\" # comment\n[build]\nsource-dirs = [\"src\", \"lib\",]\n").unwrap();
+        assert_eq!((manifest.entry.as_str(), manifest.output.as_str()), ("src/main.lat", "a"));
+        assert_eq!(manifest.source_dirs, vec!["src", "lib"]);
+        assert_eq!((manifest.profile, manifest.warnings), (Profile::Release, options::Warnings::Warn));
+        assert_eq!(Manifest::parse("[build]\nprofile = \"debug\"\n").unwrap_err(), "missing project.name");
+        assert_eq!(Manifest::parse("[project]\nname = \"a\"\nentry = [\"b\"]\n").unwrap_err(), "line 3: project.entry must be a string");
+        assert_eq!(Manifest::parse("[project]\nname = \"a\"\nouptut = \"b\"\n").unwrap_err(), "line 3: unknown key project.ouptut");
+        assert_eq!(Manifest::parse("[project]\nname = \"a\"\nname = \"b\"\n").unwrap_err(), "line 3: name is set twice");
+
+        // files are compiled after the files they import
+        let root = Path::new("./lattests/extensions/project");
+        let manifest = Manifest::load(&root.join(project::MANIFEST)).unwrap();
+        assert_eq!(manifest.warnings, options::Warnings::Deny);
+        let order: Vec<String> = project::plan(root, &manifest).unwrap().into_iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(order, vec!["math.lat", "fraction.lat", "main.lat"]);
+
+        // options given on the command line override the manifest
+        let opts = project::build_options(&manifest, &[]).unwrap();
+        assert_eq!((opts.warnings, opts.elide_null_checks), (options::Warnings::Deny, true));
+        let args: Vec<String> = ["--warnings=warn", "build"].iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(project::build_options(&manifest, &args).unwrap().warnings, options::Warnings::Warn);
+        let debug = Manifest::parse("[project]\nname = \"a\"\n[build]\nprofile = \"debug\"\n").unwrap();
+        let opts = project::build_options(&debug, &["-finline-limit=5".to_owned()]).unwrap();
+        assert_eq!((opts.inline_limit, opts.debug_info), (5, true));
+    }
+
+    #[test]
+    fn warnings() {
+        let source = "int main() {\n  return 0;\n  printInt(1);\n  printInt(2);\n}\n";
+        let (_, diags) = latte::check(source);
+        assert_eq!(diags.len(), 1);
+        assert_eq!((diags[0].code, diags[0].severity), ("W0001", diag::Severity::Warning));
+        let span = diags[0].details.as_ref().unwrap().0;
+        assert_eq!(&source[span.0..span.1], "printInt(1);");
+        assert!(explain::get("W0001").is_some());
+        // empty statements are not reported
+        assert!(latte::check("int main() {\n  return 0;\n  ;\n}\n").1.is_empty());
+
+        // deny fails the compilation, the backend does not run
+        let mut sm = SourceMap::new();
+        let file = sm.add_file("unreachable.lat", source.to_owned());
+        let opts = options::Options {warnings: options::Warnings::Deny, ..options::Options::default()};
+        let diags = process(&mut sm, file, Path::new("unreachable.lat"), &opts).unwrap_err();
+        assert_eq!(diags.iter().map(|d| d.code).collect::<Vec<&str>>(), vec!["W0001"]);
+    }
+
+    #[test]
+    fn compilation_cache() {
+        let key_of_build = |build_id: u64, source: &str, opts: &options::Options| {
+            let mut sm = SourceMap::new();
+            sm.add_file("a.lat", source.to_owned());
+            let prog = latte::parse(source).0.unwrap();
+            cache::Key::new(&prog, &sm, Path::new("a.lat"), build_id, "runtime", opts)
+        };
+        let key = |source: &str, opts: &options::Options| key_of_build(cache::build_id(), source, opts);
+        let source = "int main() {\n  return 0;\n}\n";
+        let opts = options::Options::default();
+        assert_eq!(key(source, &opts), key(source, &opts));
+        assert_ne!(key(source, &opts), key("int main() {\n  return 1;\n}\n", &opts));
+        assert_ne!(key(source, &opts), key(source, &options::Options {overflow: options::Overflow::Trap, ..opts.clone()}));
+        assert_ne!(key(source, &opts), key(source, &options::Options {optimize: false, ..opts.clone()}));
+        // diagnostics are printed before the backend runs
+        assert_eq!(key(source, &opts), key(source, &options::Options {error_format: options::ErrorFormat::Json, ..opts.clone()}));
+
+        let dir = std::env::temp_dir().join(format!("latte_cache_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = cache::Cache::new(dir.join("cache").to_str().unwrap());
+        let a_output = |ext: &str| dir.join(format!("a.{}", ext));
+        let b_output = |ext: &str| dir.join(format!("b.{}", ext));
+        let k = key(source, &opts);
+        assert!(!cache.restore(k, b_output));
+        fs::write(a_output("ll"), "; ir").unwrap();
+        fs::write(a_output("bc"), "bitcode").unwrap();
+        cache.store(k, a_output).unwrap();
+        assert!(cache.restore(k, b_output));
+        assert_eq!(fs::read_to_string(b_output("bc")).unwrap(), "bitcode");
+        assert!(cache.summary().contains("2 files (11 bytes)"), "{}", cache.summary());
+        // entries of another build of the compiler are not used
+        assert_eq!(cache::build_id(), cache::build_id());
+        assert!(!cache.restore(key_of_build(cache::build_id() ^ 1, source, &opts), b_output));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn golden_tests() {
+        use golden::Expect;
+        let cases: Vec<(String, Expect)> = golden::discover(Path::new("./lattests/extensions/modules")).unwrap().into_iter()
+            .map(|case| (case.path.file_name().unwrap().to_string_lossy().into_owned(), case.expect))
+            .collect();
+        // list.lat and strings.lat are only imported
+        assert_eq!(cases, vec![
+            ("conflict.lat".to_owned(), Expect::CompileError), ("cycle_a.lat".to_owned(), Expect::CompileError),
+            ("cycle_b.lat".to_owned(), Expect::CompileError), ("missing.lat".to_owned(), Expect::CompileError),
+            ("private.lat".to_owned(), Expect::CompileError), ("main.lat".to_owned(), Expect::Output),
+        ]);
+        // null checks, their messages are compared with .stderr files
+        let null_checks = golden::discover(Path::new("./lattests/extensions/struct/bad/runtime")).unwrap();
+        assert!(null_checks.iter().all(|case| case.expect == Expect::RuntimeError && case.path.with_extension("stderr").is_file()));
+        // a million nested calls, it passes only if the tail call is a loop
+        let hardcore = golden::discover(Path::new("./lattests/students/good/hardcore")).unwrap();
+        assert!(hardcore.iter().any(|case| case.path.ends_with("tail_call_optymization.lat") && case.expect == Expect::Output));
+        let runtime = golden::discover(Path::new("./lattests/students/bad/runtime")).unwrap();
+        assert!(runtime.iter().all(|case| case.expect == Expect::RuntimeError));
+        // only errors reported by the program count, not lli rejecting the bitcode
+        let error = &runtime[0];
+        assert_eq!(golden::check_runtime_error(error, false, "runtime error\n", false), golden::Outcome::Passed);
+        assert!(matches!(golden::check_runtime_error(error, true, "", false), golden::Outcome::Failed(_)));
+        assert!(matches!(golden::check_runtime_error(error, false, "lli: error.bc: error: Invalid bitcode signature\n", false), golden::Outcome::Failed(_)));
+        // and a program with the expected output must not fail
+        let output = golden::TestCase {path: "./lattests/extensions/checks/overflow.lat".into(), expect: Expect::Output};
+        let expected = fs::read_to_string("./lattests/extensions/checks/overflow.output").unwrap();
+        assert_eq!(golden::check_output(&output, true, &expected, "", false), golden::Outcome::Passed);
+        assert!(matches!(golden::check_output(&output, false, &expected, "", false), golden::Outcome::Failed(_)));
+        assert!(matches!(golden::check_output(&output, false, "", "lli: error: Invalid bitcode signature\n", true), golden::Outcome::Failed(_)));
+
+        let case = golden::TestCase {path: "./lattests/extensions/modules/bad/cycle_a.lat".into(), expect: Expect::CompileError};
+        assert_eq!(golden::run(&case, &options::Options::default(), &std::env::temp_dir()), golden::Outcome::Passed);
+
+        assert_eq!(golden::diff("a\nb\nc\n", "a\nx\nc\nd\n"), " a\n-b\n+x\n c\n+d\n");
+        assert_eq!(golden::diff("", "a\n"), "+a\n");
+    }
+
+    #[test]
+    fn golden_run() {
+        use golden::Expect;
+        if std::process::Command::new("lli").arg("--version").output().is_err() {
+            eprintln!("lli not found, skipping golden_run");
+            return;
+        }
+        let cases = [
+            ("./lattests/extensions/modules/bad/private.lat", Expect::CompileError),
+            // with an .stderr file
+            ("./lattests/extensions/checks/bad/runtime/div_zero.lat", Expect::RuntimeError),
+            // with a .flags file
+            ("./lattests/extensions/checks/bad/runtime/add_overflow.lat", Expect::RuntimeError),
+            ("./lattests/extensions/checks/overflow.lat", Expect::Output),
+            // with imports
+            ("./lattests/extensions/modules/main.lat", Expect::Output),
+            // fixed problems, see KNOWN_PROBLEMS.md
+            ("./lattests/students/good/basic/while_true.lat", Expect::Output),
+            ("./lattests/students/good/basic/void_return.lat", Expect::Output),
+        ];
+        let dir = std::env::temp_dir().join(format!("latte_golden_{}", std::process::id()));
+        for (path, expect) in &cases {
+            let case = golden::TestCase {path: path.into(), expect: *expect};
+            fs::create_dir_all(&dir).unwrap();
+            assert_eq!(golden::run(&case, &options::Options::default(), &dir), golden::Outcome::Passed, "{}", path);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        // the expectation is checked, not only that the file compiles and runs
+        let case = golden::TestCase {path: "./lattests/extensions/checks/overflow.lat".into(), expect: Expect::RuntimeError};
+        fs::create_dir_all(&dir).unwrap();
+        assert!(matches!(golden::run(&case, &options::Options::default(), &dir), golden::Outcome::Failed(_)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,157 @@
+// Project manifest latte.toml, read by latte build. It is a small subset of TOML: sections, and keys
+// with string or string array values, one per line.
+//
+//     [project]
+//     name = "lists"
+//     entry = "src/main.lat"     # defines main, compiled even if outside of source dirs
+//     output = "lists"           # target/lists.bc, the name by default
+//
+//     [build]
+//     source-dirs = ["src"]      # all .lat files in them are compiled
+//     profile = "release"        # or "debug"
+//     warnings = "warn"          # allow, warn or deny
+use crate::options::{Options, Warnings};
+use crate::utils::unescape_string;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Profile {
+    Debug, // debug information, no inlining
+    Release,
+}
+
+impl Profile {
+    // Defaults of the profile, options given on the command line override them.
+    pub fn apply(self, opts: &mut Options) {
+        match self {
+            Profile::Debug => {
+                opts.debug_info = true;
+                opts.inline_limit = 0;
+            },
+            Profile::Release => opts.elide_null_checks = true,
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub entry: String,
+    pub output: String,
+    pub source_dirs: Vec<String>,
+    pub profile: Profile,
+    pub warnings: Warnings,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Value {
+    Str(String),
+    Array(Vec<String>),
+}
+
+// Index of the # starting a comment, if any.
+fn find_comment(line: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+fn parse_string(text: &str) -> Option<String> {
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        Some(unescape_string(&text[1..text.len() - 1]))
+    } else {
+        None
+    }
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    if !(text.starts_with('[') && text.ends_with(']')) {
+        return parse_string(text).map(Value::Str);
+    }
+    let items = text[1..text.len() - 1].trim().trim_end_matches(',');
+    if items.trim().is_empty() {
+        return Some(Value::Array(Vec::new()));
+    }
+    // strings in arrays can't contain commas, paths rarely do
+    items.split(',').map(|item| parse_string(item.trim())).collect::<Option<_>>().map(Value::Array)
+}
+
+// Keys are "section.key".
+fn parse_toml(text: &str) -> Result<HashMap<String, (usize, Value)>, String> {
+    let mut values = HashMap::new();
+    let mut section = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = match find_comment(line) {
+            Some(comment) => &line[..comment],
+            None => line,
+        }.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim().to_owned();
+            continue;
+        }
+        let (key, value) = match line.find('=') {
+            Some(eq) => (line[..eq].trim(), line[eq + 1..].trim()),
+            None => return Err(format!("line {}: expected key = value", line_no)),
+        };
+        let value = parse_value(value).ok_or_else(|| format!("line {}: invalid value of {}", line_no, key))?;
+        if values.insert(format!("{}.{}", section, key), (line_no, value)).is_some() {
+            return Err(format!("line {}: {} is set twice", line_no, key));
+        }
+    }
+    Ok(values)
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut values = parse_toml(text)?;
+        let mut take_str = |key: &str| match values.remove(key) {
+            None => Ok(None),
+            Some((_, Value::Str(value))) => Ok(Some(value)),
+            Some((line_no, _)) => Err(format!("line {}: {} must be a string", line_no, key)),
+        };
+        let name = take_str("project.name")?.ok_or("missing project.name")?;
+        let entry = take_str("project.entry")?.unwrap_or_else(|| "src/main.lat".to_owned());
+        let output = take_str("project.output")?.unwrap_or_else(|| name.clone());
+        let profile = match take_str("build.profile")?.as_deref() {
+            None | Some("release") => Profile::Release,
+            Some("debug") => Profile::Debug,
+            Some(other) => return Err(format!("invalid profile: {} (expected debug or release)", other)),
+        };
+        let warnings = match take_str("build.warnings")?.as_deref() {
+            None | Some("warn") => Warnings::Warn,
+            Some("allow") => Warnings::Allow,
+            Some("deny") => Warnings::Deny,
+            Some(other) => return Err(format!("invalid warning policy: {} (expected allow, warn or deny)", other)),
+        };
+        let source_dirs = match values.remove("build.source-dirs") {
+            None => vec!["src".to_owned()],
+            Some((_, Value::Array(dirs))) => dirs,
+            Some((line_no, _)) => return Err(format!("line {}: build.source-dirs must be an array of strings", line_no)),
+        };
+        // typos would be silently ignored otherwise
+        if let Some((key, (line_no, _))) = values.iter().min_by_key(|(_, (line_no, _))| *line_no) {
+            return Err(format!("line {}: unknown key {}", line_no, key));
+        }
+        Ok(Manifest {name, entry, output, source_dirs, profile, warnings})
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("error while reading file {}: {}", path.display(), e))?;
+        Manifest::parse(&text).map_err(|msg| format!("{}: {}", path.display(), msg))
+    }
+}
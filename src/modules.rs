@@ -0,0 +1,406 @@
+// Programs split into several files. `import "list.lat";` makes definitions marked with `export`
+// in list.lat visible in the importing file, other definitions stay private. Every file is a module
+// with its own namespace: definitions of imported modules are renamed to "list.push", so definitions
+// of different modules never clash, and all modules are merged into one program before type checking.
+// Names in the compiled (root) file are kept, so single file programs are not changed at all.
+//
+// With -c (separate compilation) only the root file is compiled and its names get the module prefix
+// too, except main. Imported modules are not parsed, their interfaces written when they were
+// compiled with -c are read instead, see interface.rs.
+use crate::ast::*;
+use crate::diag::{self, Diagnostic};
+use crate::frontend::builtin_functions;
+use crate::interface;
+use crate::options::Options;
+use crate::parse;
+use crate::utils::is_newer;
+use crate::source_map::{FileId, SourceMap};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+struct Module {
+    name: String, // of the file
+    prefix: Option<String>, // added to names of definitions, none for the root module
+    ast: Option<Program>, // none if the file could not be parsed or only its interface was read
+    interface: Option<Interface>, // separately compiled module
+    imports: Vec<(usize, Span)>, // directly imported modules
+}
+
+// Traversal of the ast changing it in place, used for shifting spans and for renaming, and by the
+// type checker to find unknown names.
+pub(crate) trait AstMut {
+    fn span(&mut self, _span: &mut Span) {}
+    // definitions and calls of functions
+    fn function(&mut self, _ident: &mut Ident, _span: Span) {}
+    // definitions of classes and their uses in types, new and null
+    fn class(&mut self, _ident: &mut Ident, _span: Span) {}
+
+    fn type_spec(&mut self, type_spec: &mut TypeSpecifier) {
+        self.span(&mut type_spec.span);
+        if let Type::Class(ident) = &mut type_spec.ttype {
+            self.class(ident, type_spec.span);
+        }
+    }
+
+    fn decl(&mut self, decl: &mut VarDecl) {
+        self.span(&mut decl.span);
+        self.type_spec(&mut decl.type_spec);
+        for body in &mut decl.vars {
+            self.span(&mut body.span);
+            if let Some(init) = &mut body.init {
+                self.exp(init);
+            }
+        }
+    }
+
+    fn field(&mut self, node: &mut FieldNode) {
+        self.span(&mut node.span);
+        if let Field::Indirect(obj, _) = &mut node.field {
+            self.field(obj);
+        }
+    }
+
+    fn memloc(&mut self, memloc: &mut MemLoc) {
+        if let MemLoc::Field(field) = memloc {
+            self.field(field);
+        }
+    }
+
+    fn exp(&mut self, node: &mut ExpNode) {
+        self.span(&mut node.span);
+        let span = node.span;
+        match &mut node.exp {
+            Exp::Unary(_, exp) => self.exp(exp),
+            Exp::Binary(lexp, _, rexp) => {
+                self.exp(lexp);
+                self.exp(rexp);
+            },
+            Exp::Call(ident, args) => {
+                self.function(ident, span);
+                args.iter_mut().for_each(|arg| self.exp(arg));
+            },
+            Exp::Obj(memloc) => self.memloc(memloc),
+            Exp::Null(ident) | Exp::New(ident) => self.class(ident, span),
+            Exp::Int(_) | Exp::Bool(_) | Exp::Str(_) => (),
+        }
+    }
+
+    fn stmt(&mut self, node: &mut StmtNode) {
+        self.span(&mut node.span);
+        match &mut node.stmt {
+            Stmt::BStmt(stmts) => stmts.iter_mut().for_each(|stmt| self.stmt(stmt)),
+            Stmt::Decl(decl) => self.decl(decl),
+            Stmt::Ass(memloc, exp) => {
+                self.memloc(memloc);
+                self.exp(exp);
+            },
+            Stmt::Incr(memloc) | Stmt::Decr(memloc) => self.memloc(memloc),
+            Stmt::Ret(exp) | Stmt::EStmt(exp) => self.exp(exp),
+            Stmt::Cond(cond, tstmt, fstmt) => {
+                self.exp(cond);
+                self.stmt(tstmt);
+                if let Some(fstmt) = fstmt {
+                    self.stmt(fstmt);
+                }
+            },
+            Stmt::While(cond, body) => {
+                self.exp(cond);
+                self.stmt(body);
+            },
+            Stmt::VRet | Stmt::Error => (),
+        }
+    }
+
+    fn program(&mut self, prog: &mut Program) {
+        self.span(&mut prog.span);
+        prog.imports.iter_mut().for_each(|import| self.span(&mut import.span));
+        for class in &mut prog.classes {
+            self.span(&mut class.span);
+            self.class(&mut class.ident, class.span);
+            class.fields.iter_mut().for_each(|field| self.decl(field));
+        }
+        for fdef in &mut prog.functions {
+            self.span(&mut fdef.span);
+            self.function(&mut fdef.ident, fdef.span);
+            self.type_spec(&mut fdef.type_spec);
+            fdef.params.iter_mut().for_each(|param| self.decl(param));
+            self.stmt(&mut fdef.body);
+        }
+    }
+}
+
+// The parser gives offsets into the file, the source map places files one after another.
+struct ShiftSpans(usize);
+
+impl AstMut for ShiftSpans {
+    fn span(&mut self, span: &mut Span) {
+        span.0 += self.0;
+        span.1 += self.0;
+    }
+}
+
+fn mangle(prefix: &Option<String>, ident: &Ident) -> Ident {
+    match prefix {
+        Some(prefix) => format!("{}.{}", prefix, ident),
+        None => ident.clone(),
+    }
+}
+
+// Names visible in a module, mapped to names in the merged program.
+#[derive(Default)]
+struct Scope {
+    functions: HashMap<Ident, Ident>,
+    classes: HashMap<Ident, Ident>,
+    // private definitions of imported modules: name -> (merged name, file)
+    private_functions: HashMap<Ident, (Ident, String)>,
+    private_classes: HashMap<Ident, (Ident, String)>,
+}
+
+struct Rename<'a> {
+    scope: &'a Scope,
+    prefix: &'a Option<String>,
+    diags: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> Rename<'a> {
+    // Unknown names get the module prefix, so they do not find definitions of other modules
+    // and are reported by the type checker.
+    fn resolve(&mut self, kind: &str, ident: &mut Ident, span: Span) {
+        let (names, private) = match kind {
+            "function" => (&self.scope.functions, &self.scope.private_functions),
+            _ => (&self.scope.classes, &self.scope.private_classes),
+        };
+        *ident = if let Some(name) = names.get(ident) {
+            name.clone()
+        } else if let Some((name, file)) = private.get(ident) {
+            self.diags.push(diag::gen_not_exported(kind, ident, file, span));
+            name.clone()
+        } else if kind == "function" && builtin_functions().iter().any(|(builtin, _)| builtin == ident) {
+            return;
+        } else {
+            mangle(self.prefix, ident)
+        };
+    }
+}
+
+impl<'a> AstMut for Rename<'a> {
+    fn function(&mut self, ident: &mut Ident, span: Span) {
+        self.resolve("function", ident, span);
+    }
+
+    fn class(&mut self, ident: &mut Ident, span: Span) {
+        self.resolve("class", ident, span);
+    }
+}
+
+struct Loader<'a> {
+    sm: &'a mut SourceMap,
+    modules: Vec<Module>,
+    by_path: HashMap<PathBuf, usize>,
+    stack: Vec<usize>, // modules being loaded, importing one of them closes a cycle
+    diags: Vec<Diagnostic>,
+    failed_import: bool,
+    opts: &'a Options, // separate compilation with -c, interfaces are in the output directory
+}
+
+impl<'a> Loader<'a> {
+    // Module name from the file name, made unique with a number.
+    fn new_prefix(&self, path: &Path) -> String {
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("module").to_owned();
+        let taken = |prefix: &str| self.modules.iter().any(|m| m.prefix.as_deref() == Some(prefix));
+        let mut prefix = stem.clone();
+        let mut n = 1;
+        while taken(&prefix) {
+            n += 1;
+            prefix = format!("{}{}", stem, n);
+        }
+        prefix
+    }
+
+    // Parses the file and loads its imports depth first.
+    fn load(&mut self, file: FileId, path: PathBuf) -> usize {
+        let (ast, diags) = parse(self.sm.get_content(file));
+        let start = self.sm.get_start(file);
+        for mut diag in diags {
+            if let Some((span, _)) = &mut diag.details {
+                ShiftSpans(start).span(span);
+            }
+            self.diags.push(diag);
+        }
+        let ast = ast.map(|mut ast| {
+            ShiftSpans(start).program(&mut ast);
+            ast
+        });
+        let imports: Vec<(String, Span)> = ast.iter()
+            .flat_map(|ast| ast.imports.iter().map(|import| (import.path.clone(), import.span)))
+            .collect();
+
+        let module = self.modules.len();
+        let prefix = if module == 0 && !self.opts.compile_only { None } else { Some(self.new_prefix(&path)) };
+        let name = self.sm.get_name(file).to_owned();
+        self.modules.push(Module {name, prefix, ast, interface: None, imports: Vec::new()});
+        self.by_path.insert(path.clone(), module);
+        self.stack.push(module);
+
+        for (import_path, span) in imports {
+            let target = path.parent().unwrap_or(Path::new("")).join(&import_path);
+            let canonical = match fs::canonicalize(&target) {
+                Ok(canonical) => canonical,
+                Err(e) => {
+                    self.diags.push(diag::gen_import_failed(&import_path, &e, span));
+                    self.failed_import = true;
+                    continue;
+                },
+            };
+            let imported = match self.by_path.get(&canonical) {
+                Some(&imported) if self.stack.contains(&imported) => {
+                    let on_cycle = self.stack.iter().skip_while(|&&m| m != imported).chain(std::iter::once(&imported));
+                    let names: Vec<&str> = on_cycle.map(|&m| self.modules[m].name.as_str()).collect();
+                    self.diags.push(diag::gen_import_cycle(&names, span));
+                    self.failed_import = true;
+                    continue;
+                },
+                Some(&imported) => imported,
+                None if self.opts.compile_only => match self.load_interface(&target, &import_path, span) {
+                    Some(imported) => {
+                        self.by_path.insert(canonical, imported);
+                        imported
+                    },
+                    None => continue,
+                },
+                None => {
+                    let imported_file = match self.sm.load_file(&target.to_string_lossy()) {
+                        Ok(imported_file) => imported_file,
+                        Err(e) => {
+                            self.diags.push(diag::gen_import_failed(&import_path, &e, span));
+                            self.failed_import = true;
+                            continue;
+                        },
+                    };
+                    self.load(imported_file, canonical)
+                },
+            };
+            self.modules[module].imports.push((imported, span));
+        }
+
+        self.stack.pop();
+        module
+    }
+
+    // Reads the interface of a separately compiled module, which must be newer than the file.
+    fn load_interface(&mut self, target: &Path, import_path: &str, span: Span) -> Option<usize> {
+        let path = self.opts.output_path(target, "lati");
+        let result = if is_newer(&path, target) { interface::read(&path) } else { Err("no up to date interface".to_owned()) };
+        let interface = match result {
+            Ok(interface) => interface,
+            Err(reason) => {
+                self.diags.push(diag::gen_missing_interface(import_path, &reason, span));
+                self.failed_import = true;
+                return None;
+            },
+        };
+        let name = target.to_string_lossy().into_owned();
+        self.modules.push(Module {name, prefix: None, ast: None, interface: Some(interface), imports: Vec::new()});
+        Some(self.modules.len() - 1)
+    }
+}
+
+// Definitions of the module and exported definitions of modules it imports.
+fn build_scope(modules: &[Module], module: usize, diags: &mut Vec<Diagnostic>) -> Scope {
+    let mut scope = Scope::default();
+    let own = match &modules[module].ast {
+        Some(own) => own,
+        None => return scope,
+    };
+    let prefix = &modules[module].prefix;
+    for fdef in &own.functions {
+        // main of the root module keeps its name also when compiled separately
+        let name = if module == 0 && fdef.ident == "main" { fdef.ident.clone() } else { mangle(prefix, &fdef.ident) };
+        scope.functions.insert(fdef.ident.clone(), name);
+    }
+    for class in &own.classes {
+        scope.classes.insert(class.ident.clone(), mangle(prefix, &class.ident));
+    }
+
+    for &(imported, span) in &modules[module].imports {
+        let imported_prefix = &modules[imported].prefix;
+        let file = &modules[imported].name;
+        // (kind, name in the module, name in the merged program, exported)
+        let defs: Vec<(&str, &Ident, Ident, bool)> = match (&modules[imported].ast, &modules[imported].interface) {
+            (Some(ast), _) => ast.functions.iter().map(|f| ("function", &f.ident, mangle(imported_prefix, &f.ident), f.exported))
+                .chain(ast.classes.iter().map(|c| ("class", &c.ident, mangle(imported_prefix, &c.ident), c.exported)))
+                .collect(),
+            // private classes of an interface may come from other modules, they are not visible by name
+            (None, Some(interface)) => interface.functions.iter().map(|f| ("function", &f.name, f.symbol.clone(), true))
+                .chain(interface.classes.iter().filter(|c| c.exported).map(|c| ("class", &c.name, c.symbol.clone(), true)))
+                .collect(),
+            (None, None) => continue,
+        };
+        for (kind, ident, name, exported) in defs {
+            let (names, private) = match kind {
+                "function" => (&mut scope.functions, &mut scope.private_functions),
+                _ => (&mut scope.classes, &mut scope.private_classes),
+            };
+            if !exported {
+                private.insert(ident.clone(), (name, file.to_owned()));
+                continue;
+            }
+            match names.get(ident) {
+                Some(other) if *other != name => diags.push(diag::gen_conflicting_import(kind, ident, span)),
+                Some(_) => (),
+                None => {
+                    names.insert(ident.clone(), name);
+                },
+            }
+        }
+    }
+    scope
+}
+
+// Loads the file with all files it imports and merges them into one program, which is none if
+// some file could not be parsed. Spans of the program are offsets in the source map. With -c,
+// imported modules are read from their interfaces and become externs of the program.
+pub fn load_program(sm: &mut SourceMap, root: FileId, opts: &Options) -> (Option<Program>, Vec<Diagnostic>) {
+    let separate = opts.compile_only;
+    let root_path = Path::new(sm.get_name(root)).to_path_buf();
+    let root_path = fs::canonicalize(&root_path).unwrap_or(root_path);
+    let mut loader = Loader {sm, modules: Vec::new(), by_path: HashMap::new(), stack: Vec::new(), diags: Vec::new(), failed_import: false, opts};
+    loader.load(root, root_path);
+    let Loader {mut modules, mut diags, failed_import, ..} = loader;
+    if modules.iter().any(|m| m.ast.is_none() && m.interface.is_none()) {
+        return (None, diags);
+    }
+
+    // a single file program has nothing to rename, unless its names get the prefix
+    if modules.len() > 1 || separate {
+        let scopes: Vec<Scope> = (0..modules.len()).map(|m| build_scope(&modules, m, &mut diags)).collect();
+        for (module, scope) in modules.iter_mut().zip(&scopes) {
+            if let Some(ast) = &mut module.ast {
+                Rename {scope, prefix: &module.prefix, diags: &mut diags}.program(ast);
+            }
+        }
+    }
+
+    let mut modules = modules.into_iter();
+    let mut prog = modules.next().unwrap().ast.unwrap();
+    for module in modules {
+        if let Some(ast) = module.ast {
+            prog.functions.extend(ast.functions);
+            prog.classes.extend(ast.classes);
+            prog.recovered |= ast.recovered;
+        }
+        // interfaces of different modules may both list a class they use
+        if let Some(interface) = module.interface {
+            prog.externs.functions.extend(interface.functions);
+            for class in interface.classes {
+                if !prog.externs.classes.iter().any(|other| other.symbol == class.symbol) {
+                    prog.externs.classes.push(class);
+                }
+            }
+        }
+    }
+    // definitions of a missing module are unknown, functions using them are not type checked
+    prog.recovered |= failed_import;
+    (Some(prog), diags)
+}
@@ -0,0 +1,77 @@
+// Control flow graph of a function, blocks are numbered in layout order so the entry is 0.
+use either::Either;
+use inkwell::basic_block::BasicBlock;
+use inkwell::values::{FunctionValue, InstructionValue};
+
+pub type BlockIdx = usize;
+
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub succs: Vec<Vec<BlockIdx>>,
+    pub preds: Vec<Vec<BlockIdx>>,
+}
+
+impl Cfg {
+    pub fn new(function: FunctionValue) -> Cfg {
+        let mut blocks = Vec::new();
+        let mut next = function.get_first_basic_block();
+        while let Some(block) = next {
+            next = block.get_next_basic_block();
+            blocks.push(block);
+        }
+
+        let mut cfg = Cfg {succs: vec![Vec::new(); blocks.len()], preds: vec![Vec::new(); blocks.len()], blocks};
+        for from in 0..cfg.blocks.len() {
+            let terminator = match cfg.blocks[from].get_terminator() {
+                Some(terminator) => terminator,
+                None => continue,
+            };
+            // successors are the block operands of the terminator
+            for i in 0..terminator.get_num_operands() {
+                if let Some(Either::Right(block)) = terminator.get_operand(i) {
+                    let to = cfg.index_of(block).expect("branch to a block of another function");
+                    if !cfg.succs[from].contains(&to) {
+                        cfg.succs[from].push(to);
+                        cfg.preds[to].push(from);
+                    }
+                }
+            }
+        }
+        cfg
+    }
+
+    pub fn index_of(&self, block: BasicBlock) -> Option<BlockIdx> {
+        self.blocks.iter().position(|b| *b == block)
+    }
+
+    // Index of the block containing the instruction.
+    pub fn block_of(&self, inst: InstructionValue) -> Option<BlockIdx> {
+        inst.get_parent().and_then(|block| self.index_of(block))
+    }
+
+    // Blocks reachable from the entry, in reverse postorder.
+    pub fn reverse_postorder(&self) -> Vec<BlockIdx> {
+        let mut postorder = Vec::new();
+        if self.blocks.is_empty() {
+            return postorder;
+        }
+        let mut visited = vec![false; self.blocks.len()];
+        // (block, index of the next successor to visit)
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((block, next_succ)) = stack.pop() {
+            match self.succs[block].get(next_succ) {
+                Some(&succ) => {
+                    stack.push((block, next_succ + 1));
+                    if !visited[succ] {
+                        visited[succ] = true;
+                        stack.push((succ, 0));
+                    }
+                },
+                None => postorder.push(block),
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+}
@@ -0,0 +1,183 @@
+// Common subexpression elimination: an instruction computing the same pure expression as an earlier
+// instruction which dominates it is replaced by the earlier one. Inside a block this is local CSE,
+// across blocks (available expressions are passed down the dominator tree) it is global CSE.
+use super::cfg::{BlockIdx, Cfg};
+use super::dominators::Dominators;
+use crate::scoped_map::ScopedMap;
+use either::Either;
+use inkwell::IntPredicate;
+use inkwell::module::Module;
+use inkwell::types::AnyTypeEnum;
+use inkwell::values::{BasicValueEnum, FunctionValue, InstructionOpcode, InstructionValue};
+use std::collections::HashSet;
+
+// Runtime functions without side effects. Strings are immutable, so comparing them is pure,
+// but __latc_concat_str allocates a new string each time.
+const PURE_RUNTIME_FUNCTIONS: &[&str] = &["__latc_compare_str"];
+
+// Calls maintaining the shadow call stack do not make the caller impure, but are never eliminated.
+const SHADOW_STACK_FUNCTIONS: &[&str] = &["__latc_push_frame", "__latc_pop_frame", "__latc_set_line"];
+
+#[derive(Debug,Default,Clone,Copy)]
+pub struct CseStats {
+    pub local: usize, // replaced by an instruction from the same block
+    pub global: usize, // replaced by an instruction from a dominating block
+}
+
+#[derive(PartialEq,Eq,Hash,Clone)]
+struct Expr<'ctx> {
+    op: &'static str,
+    operands: Vec<BasicValueEnum<'ctx>>,
+    ttype: AnyTypeEnum<'ctx>, // casts of the same value to different types differ only here
+}
+
+// Name of the operation if it only computes a value from its operands.
+fn pure_op(inst: InstructionValue) -> Option<&'static str> {
+    Some(match inst.get_opcode() {
+        InstructionOpcode::Add => "add",
+        InstructionOpcode::Sub => "sub",
+        InstructionOpcode::Mul => "mul",
+        // if the first division traps, the repeated one is never reached
+        InstructionOpcode::SDiv => "sdiv",
+        InstructionOpcode::SRem => "srem",
+        InstructionOpcode::Xor => "xor",
+        InstructionOpcode::And => "and",
+        InstructionOpcode::Or => "or",
+        InstructionOpcode::ZExt => "zext",
+        InstructionOpcode::SExt => "sext",
+        InstructionOpcode::Trunc => "trunc",
+        InstructionOpcode::ICmp => match inst.get_icmp_predicate()? {
+            IntPredicate::EQ => "icmp eq",
+            IntPredicate::NE => "icmp ne",
+            IntPredicate::SGT => "icmp sgt",
+            IntPredicate::SGE => "icmp sge",
+            IntPredicate::SLT => "icmp slt",
+            IntPredicate::SLE => "icmp sle",
+            IntPredicate::UGT => "icmp ugt",
+            IntPredicate::UGE => "icmp uge",
+            IntPredicate::ULT => "icmp ult",
+            IntPredicate::ULE => "icmp ule",
+        },
+        _ => return None,
+    })
+}
+
+// The called function is the last operand of a call.
+fn callee_name(call: InstructionValue) -> Option<String> {
+    match call.get_operand(call.get_num_operands().checked_sub(1)?) {
+        Some(Either::Left(BasicValueEnum::PointerValue(callee))) => Some(callee.get_name().to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+fn is_pure_call(call: InstructionValue, pure_functions: &HashSet<String>) -> bool {
+    call.get_opcode() == InstructionOpcode::Call && callee_name(call).map_or(false, |name| pure_functions.contains(&name))
+}
+
+fn expr_of<'ctx>(inst: InstructionValue<'ctx>, pure_functions: &HashSet<String>) -> Option<Expr<'ctx>> {
+    let op = match pure_op(inst) {
+        Some(op) => op,
+        None if is_pure_call(inst, pure_functions) => "call",
+        None => return None,
+    };
+    let mut operands = Vec::new();
+    for i in 0..inst.get_num_operands() {
+        match inst.get_operand(i) {
+            Some(Either::Left(value)) => operands.push(value),
+            _ => return None,
+        }
+    }
+    Some(Expr {op, operands, ttype: inst.get_type()})
+}
+
+fn has_side_effects(function: FunctionValue, pure_functions: &HashSet<String>) -> bool {
+    let mut next_block = function.get_first_basic_block();
+    while let Some(block) = next_block {
+        next_block = block.get_next_basic_block();
+        let mut next_inst = block.get_first_instruction();
+        while let Some(inst) = next_inst {
+            next_inst = inst.get_next_instruction();
+            let pure = match inst.get_opcode() {
+                InstructionOpcode::Phi | InstructionOpcode::Br | InstructionOpcode::Return |
+                InstructionOpcode::Unreachable => true,
+                InstructionOpcode::Call => is_pure_call(inst, pure_functions) ||
+                    callee_name(inst).map_or(false, |name| SHADOW_STACK_FUNCTIONS.contains(&name.as_str())),
+                _ => pure_op(inst).is_some(),
+            };
+            if !pure {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// Names of functions which only compute a value from their arguments: their bodies contain pure
+// instructions and calls to pure functions. Starts from all functions and removes the impure ones
+// until nothing changes, so mutually recursive pure functions stay pure.
+pub fn find_pure_functions(md: &Module) -> HashSet<String> {
+    let mut defined = Vec::new();
+    let mut next = md.get_first_function();
+    while let Some(function) = next {
+        next = function.get_next_function();
+        if function.get_first_basic_block().is_some() {
+            defined.push(function);
+        }
+    }
+
+    let mut pure: HashSet<String> = PURE_RUNTIME_FUNCTIONS.iter().map(|name| name.to_string()).collect();
+    pure.extend(defined.iter().map(|f| f.get_name().to_string_lossy().into_owned()));
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for function in &defined {
+            let name = function.get_name().to_string_lossy().into_owned();
+            if pure.contains(&name) && has_side_effects(*function, &pure) {
+                pure.remove(&name);
+                changed = true;
+            }
+        }
+    }
+    pure
+}
+
+fn visit<'ctx>(cfg: &Cfg, children: &[Vec<BlockIdx>], block: BlockIdx, pure_functions: &HashSet<String>,
+               available: &mut ScopedMap<Expr<'ctx>, (BlockIdx, InstructionValue<'ctx>)>, stats: &mut CseStats) {
+    available.push_scope();
+    let mut next = cfg.blocks[block].get_first_instruction();
+    while let Some(inst) = next {
+        next = inst.get_next_instruction();
+        let expr = match expr_of(inst, pure_functions) {
+            Some(expr) => expr,
+            None => continue,
+        };
+        match available.get(&expr) {
+            Some(&(prev_block, prev)) => {
+                if prev_block == block {
+                    stats.local += 1;
+                } else {
+                    stats.global += 1;
+                }
+                inst.replace_all_uses_with(&prev);
+                inst.erase_from_basic_block();
+            },
+            None => {
+                available.insert_into_top_scope(expr, (block, inst));
+            },
+        }
+    }
+    for &child in &children[block] {
+        visit(cfg, children, child, pure_functions, available, stats);
+    }
+    available.pop_scope();
+}
+
+pub fn eliminate_common_subexpressions(cfg: &Cfg, doms: &Dominators, pure_functions: &HashSet<String>) -> CseStats {
+    let mut stats = CseStats::default();
+    let children = doms.children();
+    // unreachable blocks are not in the dominator tree and are left alone
+    if let Some(&entry) = cfg.reverse_postorder().first() {
+        visit(cfg, &children, entry, pure_functions, &mut ScopedMap::new(), &mut stats);
+    }
+    stats
+}
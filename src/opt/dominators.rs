@@ -0,0 +1,92 @@
+// Dominator tree, computed with "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy.
+use super::cfg::{BlockIdx, Cfg};
+
+pub struct Dominators {
+    idom: Vec<Option<BlockIdx>>, // immediate dominator, none for the entry and unreachable blocks
+    rpo_number: Vec<usize>,
+}
+
+impl Dominators {
+    pub fn new(cfg: &Cfg) -> Dominators {
+        let rpo = cfg.reverse_postorder();
+        let mut rpo_number = vec![usize::max_value(); cfg.blocks.len()];
+        for (number, &block) in rpo.iter().enumerate() {
+            rpo_number[block] = number;
+        }
+
+        let mut idom: Vec<Option<BlockIdx>> = vec![None; cfg.blocks.len()];
+        if rpo.is_empty() {
+            return Dominators {idom, rpo_number};
+        }
+        let entry = rpo[0];
+        idom[entry] = Some(entry);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in rpo.iter().skip(1) {
+                let mut new_idom = None;
+                for &pred in &cfg.preds[block] {
+                    if idom[pred].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(other) => intersect(&idom, &rpo_number, pred, other),
+                    });
+                }
+                if new_idom.is_some() && idom[block] != new_idom {
+                    idom[block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        idom[entry] = None;
+        Dominators {idom, rpo_number}
+    }
+
+    pub fn is_reachable(&self, block: BlockIdx) -> bool {
+        self.rpo_number[block] != usize::max_value()
+    }
+
+    // Children of every block in the dominator tree.
+    pub fn children(&self) -> Vec<Vec<BlockIdx>> {
+        let mut children = vec![Vec::new(); self.idom.len()];
+        for (block, idom) in self.idom.iter().enumerate() {
+            if let Some(parent) = idom {
+                children[*parent].push(block);
+            }
+        }
+        children
+    }
+
+    // Every path from the entry to b goes through a, a block dominates itself.
+    pub fn dominates(&self, a: BlockIdx, b: BlockIdx) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        let mut block = b;
+        loop {
+            if block == a {
+                return true;
+            }
+            match self.idom[block] {
+                Some(parent) => block = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+// Finds the closest common dominator by walking up the tree being built.
+fn intersect(idom: &[Option<BlockIdx>], rpo_number: &[usize], a: BlockIdx, b: BlockIdx) -> BlockIdx {
+    let (mut a, mut b) = (a, b);
+    while a != b {
+        while rpo_number[a] > rpo_number[b] {
+            a = idom[a].unwrap();
+        }
+        while rpo_number[b] > rpo_number[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
@@ -0,0 +1,63 @@
+// Loop invariant code motion: pure instructions whose operands do not change in the loop
+// are moved to the preheader, so they are computed once instead of in every iteration.
+use super::cfg::Cfg;
+use super::loops::Loop;
+use either::Either;
+use inkwell::builder::Builder;
+use inkwell::values::{InstructionOpcode, InstructionValue};
+
+// Instructions without side effects which cannot trap, so they may run even if the loop does not.
+fn is_hoistable(inst: InstructionValue) -> bool {
+    match inst.get_opcode() {
+        InstructionOpcode::Add | InstructionOpcode::Sub | InstructionOpcode::Mul |
+        InstructionOpcode::Xor | InstructionOpcode::And | InstructionOpcode::Or |
+        InstructionOpcode::ICmp | InstructionOpcode::ZExt | InstructionOpcode::SExt | InstructionOpcode::Trunc => true,
+        // division by zero and INT_MIN / -1 are undefined, only constant safe divisors are allowed
+        InstructionOpcode::SDiv | InstructionOpcode::SRem => match inst.get_operand(1) {
+            Some(Either::Left(divisor)) => match divisor.into_int_value().get_sign_extended_constant() {
+                Some(d) => d != 0 && d != -1,
+                None => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn has_invariant_operands(cfg: &Cfg, lp: &Loop, inst: InstructionValue) -> bool {
+    (0..inst.get_num_operands()).all(|i| match inst.get_operand(i) {
+        Some(Either::Left(value)) => lp.is_invariant(cfg, value),
+        _ => false,
+    })
+}
+
+// Returns the number of hoisted instructions.
+pub fn hoist_invariants(cfg: &Cfg, lp: &Loop, builder: &Builder) -> usize {
+    let preheader = match lp.preheader(cfg) {
+        Some(preheader) => preheader,
+        None => return 0,
+    };
+    let terminator = cfg.blocks[preheader].get_terminator().unwrap();
+
+    let mut hoisted = 0;
+    let mut changed = true;
+    // blocks are visited in reverse postorder, so usually operands are hoisted before their users
+    // and one pass is enough
+    while changed {
+        changed = false;
+        for &block in &lp.blocks {
+            let mut next = cfg.blocks[block].get_first_instruction();
+            while let Some(inst) = next {
+                next = inst.get_next_instruction();
+                if is_hoistable(inst) && has_invariant_operands(cfg, lp, inst) {
+                    inst.remove_from_basic_block();
+                    builder.position_before(&terminator);
+                    builder.insert_instruction(&inst, None);
+                    hoisted += 1;
+                    changed = true;
+                }
+            }
+        }
+    }
+    hoisted
+}
@@ -0,0 +1,63 @@
+// Natural loops: a back edge goes from a block to its dominator (the header), the loop consists
+// of blocks which reach the back edge without passing through the header.
+use super::cfg::{BlockIdx, Cfg};
+use super::dominators::Dominators;
+use inkwell::values::{BasicValue, BasicValueEnum};
+
+pub struct Loop {
+    pub header: BlockIdx,
+    pub latches: Vec<BlockIdx>, // sources of back edges
+    pub blocks: Vec<BlockIdx>, // in reverse postorder, the header is first
+    in_loop: Vec<bool>,
+}
+
+impl Loop {
+    pub fn contains(&self, block: BlockIdx) -> bool {
+        self.in_loop[block]
+    }
+
+    // Constants, arguments and instructions placed outside of the loop do not change in it.
+    pub fn is_invariant(&self, cfg: &Cfg, value: BasicValueEnum) -> bool {
+        match value.as_instruction_value() {
+            None => true,
+            Some(inst) => cfg.block_of(inst).map_or(false, |block| !self.contains(block)),
+        }
+    }
+
+    // The only block outside of the loop which enters it, if it jumps unconditionally to the header.
+    // Code placed at its end runs once before the loop.
+    pub fn preheader(&self, cfg: &Cfg) -> Option<BlockIdx> {
+        let outside: Vec<BlockIdx> = cfg.preds[self.header].iter().cloned().filter(|&b| !self.contains(b)).collect();
+        match outside.as_slice() {
+            [pred] if cfg.succs[*pred] == [self.header] => Some(*pred),
+            _ => None,
+        }
+    }
+}
+
+// Returns loops of the function, inner loops go before the loops containing them.
+pub fn find_loops(cfg: &Cfg, doms: &Dominators) -> Vec<Loop> {
+    let rpo = cfg.reverse_postorder();
+    let mut loops: Vec<Loop> = Vec::new();
+    for &header in &rpo {
+        let latches: Vec<BlockIdx> = cfg.preds[header].iter().cloned().filter(|&b| doms.dominates(header, b)).collect();
+        if latches.is_empty() {
+            continue;
+        }
+
+        let mut in_loop = vec![false; cfg.blocks.len()];
+        in_loop[header] = true;
+        let mut worklist = latches.clone();
+        while let Some(block) = worklist.pop() {
+            if !in_loop[block] {
+                in_loop[block] = true;
+                worklist.extend(cfg.preds[block].iter().filter(|&&pred| doms.is_reachable(pred)));
+            }
+        }
+        let blocks = rpo.iter().cloned().filter(|&b| in_loop[b]).collect();
+        loops.push(Loop {header, latches, blocks, in_loop});
+    }
+    // a loop nested in another one has fewer blocks
+    loops.sort_by_key(|l| l.blocks.len());
+    loops
+}
@@ -0,0 +1,64 @@
+// Optimizations of the generated LLVM IR, done by the compiler itself so they do not depend on opt -O.
+mod cfg;
+mod cse;
+mod dominators;
+mod licm;
+mod loops;
+mod strength;
+
+use inkwell::context::Context;
+use inkwell::module::Module;
+use inkwell::values::FunctionValue;
+use std::collections::HashSet;
+use std::fmt;
+
+// Numbers of changes made by the passes, printed with --opt-stats.
+#[derive(Debug,Default,Clone,Copy)]
+pub struct Stats {
+    pub cse: cse::CseStats,
+    pub hoisted: usize,
+    pub reduced: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "cse: {} expressions eliminated ({} local, {} global)",
+                 self.cse.local + self.cse.global, self.cse.local, self.cse.global)?;
+        writeln!(f, "licm: {} instructions hoisted", self.hoisted)?;
+        write!(f, "strength reduction: {} multiplications replaced", self.reduced)
+    }
+}
+
+fn optimize_function(llvm: &Context, function: FunctionValue, pure_functions: &HashSet<String>, stats: &mut Stats) {
+    let cfg = cfg::Cfg::new(function);
+    let doms = dominators::Dominators::new(&cfg);
+    let loops = loops::find_loops(&cfg, &doms);
+    // the passes only move and replace instructions, the cfg stays valid
+
+    let cse_stats = cse::eliminate_common_subexpressions(&cfg, &doms, pure_functions);
+    stats.cse.local += cse_stats.local;
+    stats.cse.global += cse_stats.global;
+
+    let builder = llvm.create_builder();
+    // inner loops go first, so their invariants can be hoisted further from the outer loops
+    for lp in &loops {
+        stats.hoisted += licm::hoist_invariants(&cfg, lp, &builder);
+    }
+    for lp in &loops {
+        stats.reduced += strength::reduce_multiplications(llvm, &cfg, lp, &builder);
+    }
+}
+
+// Runs the optimizations on all functions defined in the module.
+pub fn optimize_module(llvm: &Context, md: &Module) -> Stats {
+    let pure_functions = cse::find_pure_functions(md);
+    let mut stats = Stats::default();
+    let mut next = md.get_first_function();
+    while let Some(function) = next {
+        next = function.get_next_function();
+        if function.get_first_basic_block().is_some() {
+            optimize_function(llvm, function, &pure_functions, &mut stats);
+        }
+    }
+    stats
+}
@@ -0,0 +1,136 @@
+// Strength reduction of multiplications by induction variables: for a basic induction variable
+// i = phi(init, i + step) and loop invariant k, i * k is replaced by a new induction variable
+// j = phi(init * k, j + step * k), so the loop adds instead of multiplying.
+use super::cfg::{BlockIdx, Cfg};
+use super::loops::Loop;
+use either::Either;
+use inkwell::builder::Builder;
+use inkwell::context::Context;
+use inkwell::values::{BasicValue, BasicValueEnum, InstructionOpcode, InstructionValue};
+
+struct InductionVar<'ctx> {
+    phi: InstructionValue<'ctx>,
+    init: BasicValueEnum<'ctx>, // value entering the loop
+    next: InstructionValue<'ctx>, // i + step, the value coming from the latch
+    step: i64,
+}
+
+fn operand<'ctx>(inst: InstructionValue<'ctx>, i: u32) -> Option<BasicValueEnum<'ctx>> {
+    match inst.get_operand(i) {
+        Some(Either::Left(value)) => Some(value),
+        _ => None,
+    }
+}
+
+fn is_value_of(value: BasicValueEnum, inst: InstructionValue) -> bool {
+    value.as_instruction_value() == Some(inst)
+}
+
+fn constant(value: BasicValueEnum) -> Option<i64> {
+    match value {
+        BasicValueEnum::IntValue(v) => v.get_sign_extended_constant(),
+        _ => None,
+    }
+}
+
+// Step of `next` if it is phi + c, c + phi or phi - c.
+fn get_step(phi: InstructionValue, next: InstructionValue) -> Option<i64> {
+    let (lhs, rhs) = (operand(next, 0)?, operand(next, 1)?);
+    match next.get_opcode() {
+        InstructionOpcode::Add if is_value_of(lhs, phi) => constant(rhs),
+        InstructionOpcode::Add if is_value_of(rhs, phi) => constant(lhs),
+        InstructionOpcode::Sub if is_value_of(lhs, phi) => constant(rhs).map(|c| -c),
+        _ => None,
+    }
+}
+
+fn find_induction_vars<'ctx>(cfg: &Cfg, lp: &Loop) -> Vec<InductionVar<'ctx>> {
+    let mut ivs = Vec::new();
+    let mut next_inst = cfg.blocks[lp.header].get_first_instruction();
+    while let Some(phi) = next_inst {
+        if phi.get_opcode() != InstructionOpcode::Phi {
+            break;
+        }
+        next_inst = phi.get_next_instruction();
+        if phi.get_num_operands() != 2 {
+            continue;
+        }
+        let (a, b) = match (operand(phi, 0), operand(phi, 1)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => continue,
+        };
+        for &(init, next) in &[(a, b), (b, a)] {
+            if !lp.is_invariant(cfg, init) || lp.is_invariant(cfg, next) {
+                continue;
+            }
+            let next = next.as_instruction_value().unwrap();
+            if let Some(step) = get_step(phi, next) {
+                ivs.push(InductionVar {phi, init, next, step});
+                break;
+            }
+        }
+    }
+    ivs
+}
+
+// Replaces i * k by a new induction variable, returns the number of replaced multiplications.
+pub fn reduce_multiplications(llvm: &Context, cfg: &Cfg, lp: &Loop, builder: &Builder) -> usize {
+    // the phis are built with incoming values from the preheader and the only latch
+    let preheader = match lp.preheader(cfg) {
+        Some(preheader) => preheader,
+        None => return 0,
+    };
+    let latch: BlockIdx = match lp.latches.as_slice() {
+        [latch] if cfg.preds[lp.header].len() == 2 => *latch,
+        _ => return 0,
+    };
+    let ivs = find_induction_vars(cfg, lp);
+    if ivs.is_empty() {
+        return 0;
+    }
+
+    let mut reduced = 0;
+    for &block in &lp.blocks {
+        let mut next_inst = cfg.blocks[block].get_first_instruction();
+        while let Some(mul) = next_inst {
+            next_inst = mul.get_next_instruction();
+            if mul.get_opcode() != InstructionOpcode::Mul {
+                continue;
+            }
+            let (lhs, rhs) = match (operand(mul, 0), operand(mul, 1)) {
+                (Some(lhs), Some(rhs)) => (lhs, rhs),
+                _ => continue,
+            };
+            let found = ivs.iter().find_map(|iv| {
+                if is_value_of(lhs, iv.phi) && lp.is_invariant(cfg, rhs) {
+                    Some((iv, rhs))
+                } else if is_value_of(rhs, iv.phi) && lp.is_invariant(cfg, lhs) {
+                    Some((iv, lhs))
+                } else {
+                    None
+                }
+            });
+            let (iv, k) = match found {
+                Some(found) => found,
+                None => continue,
+            };
+
+            // arithmetic wraps, so init * k + n * (step * k) == (init + n * step) * k
+            let k = k.into_int_value();
+            builder.position_before(&cfg.blocks[preheader].get_terminator().unwrap());
+            let init = builder.build_int_mul(iv.init.into_int_value(), k, "iv_init");
+            let step = builder.build_int_mul(llvm.i32_type().const_int(iv.step as u64, true), k, "iv_step");
+
+            builder.position_before(&cfg.blocks[lp.header].get_first_instruction().unwrap());
+            let phi = builder.build_phi(llvm.i32_type(), "iv");
+            builder.position_before(&iv.next.get_next_instruction().unwrap());
+            let next = builder.build_int_add(phi.as_basic_value().into_int_value(), step, "iv_next");
+            phi.add_incoming(&[(&init, &cfg.blocks[preheader]), (&next, &cfg.blocks[latch])]);
+
+            mul.replace_all_uses_with(&phi.as_instruction());
+            mul.erase_from_basic_block();
+            reduced += 1;
+        }
+    }
+    reduced
+}
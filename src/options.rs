@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+// What to print instead of compiling, see --emit.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Emit {
+    Ast,
+    TypedAst,
+    AstJson,
+    TypedAstJson,
+}
+
+// Semantics of int arithmetic overflow at runtime, see --overflow.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Overflow {
+    Wrap, // two's complement wrap around
+    Trap, // runtime error
+}
+
+// What to do with warnings, see --warnings.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Warnings {
+    Allow, // not reported
+    Warn, // reported, compilation succeeds
+    Deny, // reported, compilation fails
+}
+
+#[derive(Debug,Clone)]
+pub struct Options {
+    pub error_format: ErrorFormat,
+    pub explain: Option<String>,
+    pub check: bool, // latte fmt: only report files that are not formatted
+    pub emit: Option<Emit>,
+    pub inline_limit: usize, // max size of an inlined function body in ast nodes, 0 disables inlining
+    pub dump_ir: bool, // print the IR before and after optimizations
+    pub opt_stats: bool, // print how many changes the optimizations made
+    pub optimize: bool, // run the optimizations of src/opt, see -fno-opt
+    pub overflow: Overflow,
+    pub elide_null_checks: bool, // skip null checks of objects proven to be non-null
+    pub stack_trace: bool, // maintain a shadow call stack printed on runtime errors
+    pub debug_info: bool, // emit DWARF debug information
+    pub compile_only: bool, // compile the file alone and write its interface, see latte link
+    pub output: Option<String>, // latte link: the linked program
+    pub out_dir: Option<String>, // where .ll, .bc and .lati files are written, next to the source by default
+    pub warnings: Warnings,
+    pub cache_dir: Option<String>, // where compiled modules are cached, see --cache
+    pub verbose: bool,
+    pub bless: bool, // latte test: overwrite expected outputs that differ
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {error_format: ErrorFormat::Human, explain: None, check: false, emit: None, inline_limit: 20, dump_ir: false, opt_stats: false, optimize: true, overflow: Overflow::Wrap, elide_null_checks: false, stack_trace: true, debug_info: false, compile_only: false, output: None, out_dir: None, warnings: Warnings::Warn, cache_dir: None, verbose: false, bless: false}
+    }
+}
+
+impl Options {
+    // File written for the source, e.g. dir/foo.bc for foo.lat.
+    pub fn output_path(&self, source: &Path, extension: &str) -> PathBuf {
+        let dir = match &self.out_dir {
+            Some(dir) => Path::new(dir),
+            None => source.parent().unwrap_or(Path::new("")),
+        };
+        dir.join(format!("{}.{}", source.file_stem().unwrap().to_string_lossy(), extension))
+    }
+
+    // Options read by the backend, others do not change the output.
+    pub fn codegen_options(&self) -> String {
+        format!("{:?} {} {} {} {} {} {}", self.overflow, self.inline_limit, self.optimize,
+                self.elide_null_checks, self.stack_trace, self.debug_info, self.compile_only)
+    }
+}
+
+// Splits command line arguments into options and positional arguments.
+pub fn parse_args(args: &[String]) -> Result<(Options, Vec<String>), String> {
+    parse_args_with(Options::default(), args)
+}
+
+// As parse_args, but options which are not given keep their values from defaults.
+pub fn parse_args_with(defaults: Options, args: &[String]) -> Result<(Options, Vec<String>), String> {
+    let mut options = defaults;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--explain" {
+            match args.next() {
+                Some(code) => options.explain = Some(code.clone()),
+                None => return Err(format!("option --explain requires an error code")),
+            }
+        }
+        else if arg == "--check" {
+            options.check = true;
+        }
+        else if arg == "--dump-ir" {
+            options.dump_ir = true;
+        }
+        else if arg == "--opt-stats" {
+            options.opt_stats = true;
+        }
+        else if arg == "-fno-opt" {
+            options.optimize = false;
+        }
+        else if arg == "-felide-null-checks" {
+            options.elide_null_checks = true;
+        }
+        else if arg == "--no-stack-trace" {
+            options.stack_trace = false;
+        }
+        else if arg == "-g" {
+            options.debug_info = true;
+        }
+        else if arg == "--bless" {
+            options.bless = true;
+        }
+        else if arg == "--verbose" {
+            options.verbose = true;
+        }
+        else if arg == "--cache" {
+            options.cache_dir = Some(".latte-cache".to_owned());
+        }
+        else if arg.starts_with("--cache=") {
+            options.cache_dir = Some(arg["--cache=".len()..].to_owned());
+        }
+        else if arg == "-c" {
+            options.compile_only = true;
+        }
+        else if arg == "-o" {
+            match args.next() {
+                Some(output) => options.output = Some(output.clone()),
+                None => return Err(format!("option -o requires a file name")),
+            }
+        }
+        else if arg.starts_with("--error-format=") {
+            options.error_format = match &arg["--error-format=".len()..] {
+                "human" => ErrorFormat::Human,
+                "json" => ErrorFormat::Json,
+                other => return Err(format!("invalid error format: {} (expected human or json)", other)),
+            };
+        }
+        else if arg.starts_with("--warnings=") {
+            options.warnings = match &arg["--warnings=".len()..] {
+                "allow" => Warnings::Allow,
+                "warn" => Warnings::Warn,
+                "deny" => Warnings::Deny,
+                other => return Err(format!("invalid warning policy: {} (expected allow, warn or deny)", other)),
+            };
+        }
+        else if arg.starts_with("--out-dir=") {
+            options.out_dir = Some(arg["--out-dir=".len()..].to_owned());
+        }
+        else if arg.starts_with("--overflow=") {
+            options.overflow = match &arg["--overflow=".len()..] {
+                "wrap" => Overflow::Wrap,
+                "trap" => Overflow::Trap,
+                other => return Err(format!("invalid overflow mode: {} (expected wrap or trap)", other)),
+            };
+        }
+        else if arg.starts_with("--emit=") {
+            options.emit = Some(match &arg["--emit=".len()..] {
+                "ast" => Emit::Ast,
+                "typed-ast" => Emit::TypedAst,
+                "ast-json" => Emit::AstJson,
+                "typed-ast-json" => Emit::TypedAstJson,
+                other => return Err(format!("invalid emit kind: {} (expected ast, typed-ast, ast-json or typed-ast-json)", other)),
+            });
+        }
+        else if arg.starts_with("-finline-limit=") {
+            options.inline_limit = match arg["-finline-limit=".len()..].parse() {
+                Ok(limit) => limit,
+                Err(_) => return Err(format!("invalid inline limit: {} (expected a non-negative number)", &arg["-finline-limit=".len()..])),
+            };
+        }
+        else if arg.starts_with("-") {
+            return Err(format!("unknown option: {}", arg));
+        }
+        else {
+            positional.push(arg.clone());
+        }
+    }
+    Ok((options, positional))
+}
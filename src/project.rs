@@ -0,0 +1,165 @@
+// latte build and latte clean: projects described by latte.toml (see manifest.rs) are compiled file by
+// file with -c into target/modules/ and linked into target/<output>.bc. Files are compiled after the files
+// they import, and only if the source, the interface of an imported file or the manifest changed
+// since the last build. target/options records the code generation options of the last build,
+// all files are compiled again when they change.
+use crate::{backend, diag, options, process};
+use crate::manifest::Manifest;
+use crate::options::Options;
+use crate::source_map::SourceMap;
+use crate::utils::is_newer;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST: &str = "latte.toml";
+pub const TARGET: &str = "target";
+
+// The directory with the manifest, the given one or one of its parents.
+pub fn find_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find(|dir| dir.join(MANIFEST).is_file()).map(Path::to_path_buf)
+}
+
+fn find_sources(dir: &Path, sources: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            find_sources(&path, sources)?;
+        } else if path.extension().map_or(false, |ext| ext == "lat") {
+            sources.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Files imported by the file, files that can't be parsed or found are left to the compiler to report.
+fn find_imports(path: &Path) -> Vec<PathBuf> {
+    let ast = match fs::read_to_string(path) {
+        Ok(source) => crate::parse(&source).0,
+        Err(_) => None,
+    };
+    ast.map_or(Vec::new(), |ast| ast.imports.iter()
+        .map(|import| path.parent().unwrap_or(Path::new("")).join(&import.path))
+        .filter_map(|import| fs::canonicalize(import).ok())
+        .collect())
+}
+
+// Files of the project with the files they import, in the order of compilation. Imported files
+// outside of the source directories are included.
+pub fn plan(root: &Path, manifest: &Manifest) -> Result<Vec<(PathBuf, Vec<PathBuf>)>, String> {
+    let mut sources = Vec::new();
+    for dir in &manifest.source_dirs {
+        find_sources(&root.join(dir), &mut sources).map_err(|e| format!("error while reading directory {}: {}", dir, e))?;
+    }
+    let entry = root.join(&manifest.entry);
+    sources.push(entry.clone());
+    let mut canonical = Vec::new();
+    for path in &sources {
+        let path = fs::canonicalize(path).map_err(|e| format!("error while reading file {}: {}", path.display(), e))?;
+        if !canonical.contains(&path) {
+            canonical.push(path);
+        }
+    }
+
+    fn visit(path: PathBuf, visited: &mut HashSet<PathBuf>, order: &mut Vec<(PathBuf, Vec<PathBuf>)>) {
+        // an import cycle is reported when the first file on it is compiled
+        if !visited.insert(path.clone()) {
+            return;
+        }
+        let imports = find_imports(&path);
+        for import in &imports {
+            visit(import.clone(), visited, order);
+        }
+        order.push((path, imports));
+    }
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    for path in canonical {
+        visit(path, &mut visited, &mut order);
+    }
+
+    // outputs of modules are named after files and so are their symbols
+    let mut stems: HashMap<&str, &Path> = HashMap::new();
+    for (path, _) in &order {
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+        if let Some(other) = stems.insert(stem, path) {
+            return Err(format!("{} and {} have the same name, names of files in a project must be unique", other.display(), path.display()));
+        }
+    }
+    Ok(order)
+}
+
+// Options of the build: the manifest gives defaults, the command line arguments override them.
+pub fn build_options(manifest: &Manifest, args: &[String]) -> Result<Options, String> {
+    let mut defaults = Options {warnings: manifest.warnings, ..Options::default()};
+    manifest.profile.apply(&mut defaults);
+    let (opts, _) = options::parse_args_with(defaults, args)?;
+    Ok(opts)
+}
+
+// Builds the project in the root directory with the command line arguments, returns the linked program.
+pub fn build(root: &Path, args: &[String]) -> Result<PathBuf, String> {
+    let manifest_path = root.join(MANIFEST);
+    let manifest = Manifest::load(&manifest_path)?;
+    let order = plan(root, &manifest)?;
+
+    let target = root.join(TARGET);
+    let modules_dir = target.join("modules");
+    fs::create_dir_all(&modules_dir).map_err(|e| format!("error while creating directory {}: {}", modules_dir.display(), e))?;
+    let mut opts = build_options(&manifest, args)?;
+    opts.compile_only = true;
+    opts.out_dir = Some(modules_dir.to_string_lossy().into_owned());
+    // written after all files are compiled, so a failed build leaves the old options
+    let stamp_path = target.join("options");
+    let stamp = opts.codegen_options();
+    let same_options = fs::read_to_string(&stamp_path).map_or(false, |old| old == stamp);
+
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut outputs = Vec::new();
+    let mut changed = false;
+    for (path, imports) in &order {
+        let output = opts.output_path(path, "bc");
+        let fresh = same_options
+            && is_newer(&output, path)
+            && is_newer(&opts.output_path(path, "lati"), path)
+            && is_newer(&output, &manifest_path)
+            && imports.iter().all(|import| is_newer(&output, &opts.output_path(import, "lati")));
+        if !fresh {
+            let name = path.strip_prefix(&canonical_root).unwrap_or(path);
+            eprintln!("Compiling {}", name.display());
+            let mut sm = SourceMap::new();
+            let file = sm.load_file(&path.to_string_lossy())
+                .map_err(|e| format!("error while reading file {}: {}", path.display(), e))?;
+            match process(&mut sm, file, path, &opts) {
+                Err(diags) => {
+                    diag::print(&diags, &sm, opts.error_format);
+                    return Err(format!("could not compile {}", name.display()));
+                },
+                Ok(warnings) => diag::print(&warnings, &sm, opts.error_format),
+            }
+            changed = true;
+        }
+        outputs.push(output);
+    }
+    if !same_options {
+        fs::write(&stamp_path, &stamp).map_err(|e| format!("error while writing file {}: {}", stamp_path.display(), e))?;
+    }
+
+    let program = target.join(format!("{}.bc", manifest.output));
+    if changed || !outputs.iter().all(|output| is_newer(&program, output)) {
+        let inputs: Vec<&Path> = outputs.iter().map(PathBuf::as_path).collect();
+        backend::link(&inputs, &program)?;
+    }
+    eprintln!("Finished {}", program.display());
+    Ok(program)
+}
+
+pub fn clean(root: &Path) -> Result<(), String> {
+    let target = root.join(TARGET);
+    if target.exists() {
+        fs::remove_dir_all(&target).map_err(|e| format!("error while removing directory {}: {}", target.display(), e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+// Source files of a compilation and mapping of spans to lines and columns. Files are laid out one
+// after another in a single offset space, so an offset alone identifies the file: the first file
+// starts at 0 and spans of its ast are plain byte offsets into its text.
+use crate::ast::Span;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub type FileId = usize;
+
+// Lines and columns are 1-based, columns are counted in characters.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Location {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
+}
+
+struct SourceFile {
+    name: String,
+    start: usize, // offset of the first byte of the file
+    line_starts: Vec<usize>, // local offsets
+    codespan_id: codespan::FileId,
+}
+
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    file_db: codespan::Files,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap {files: Vec::new(), file_db: codespan::Files::default()}
+    }
+
+    pub fn load_file(&mut self, name: &str) -> Result<FileId, io::Error> {
+        let content = fs::read_to_string(Path::new(name))?;
+        Ok(self.add_file(name, content))
+    }
+
+    pub fn add_file(&mut self, name: &str, content: String) -> FileId {
+        // one byte gap, so the end of a file is not the start of the next one
+        let start = self.files.last().map_or(0, |last| last.start + self.file_db.source(last.codespan_id).len() + 1);
+        let line_starts = std::iter::once(0).chain(content.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        let codespan_id = self.file_db.add(name, content);
+        self.files.push(SourceFile {name: name.to_owned(), start, line_starts, codespan_id});
+        self.files.len() - 1
+    }
+
+    pub fn len(&self) -> usize { self.files.len() }
+
+    pub fn is_empty(&self) -> bool { self.files.is_empty() }
+
+    pub fn get_name(&self, file: FileId) -> &str { &self.files[file].name }
+
+    pub fn get_content(&self, file: FileId) -> &str { self.file_db.source(self.files[file].codespan_id) }
+
+    pub fn get_start(&self, file: FileId) -> usize { self.files[file].start }
+
+    // File containing the offset, offsets past the end belong to the last file.
+    pub fn lookup_file(&self, offset: usize) -> FileId {
+        match self.files.binary_search_by_key(&offset, |file| file.start) {
+            Ok(file) => file,
+            Err(next) => next.saturating_sub(1),
+        }
+    }
+
+    pub fn get_location(&self, offset: usize) -> Location {
+        let file = self.lookup_file(offset);
+        let content = self.get_content(file);
+        let local = (offset - self.files[file].start).min(content.len());
+        let line_starts = &self.files[file].line_starts;
+        let line = match line_starts.binary_search(&local) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let column = content[line_starts[line]..].char_indices().take_while(|(i, _)| line_starts[line] + i < local).count() + 1;
+        Location {file, line: line + 1, column}
+    }
+
+    // Inverse of get_location, none if the line or column is out of the file.
+    pub fn get_offset(&self, location: Location) -> Option<usize> {
+        let file = &self.files[location.file];
+        let content = self.get_content(location.file);
+        let line_start = *file.line_starts.get(location.line.checked_sub(1)?)?;
+        let line = content[line_start..].split('\n').next().unwrap();
+        let local = match location.column.checked_sub(1)? {
+            column if column == line.chars().count() => line_start + line.len(),
+            column => line_start + line.char_indices().nth(column)?.0,
+        };
+        Some(file.start + local)
+    }
+
+    // "name:line:column", as in runtime error messages.
+    pub fn format_location(&self, offset: usize) -> String {
+        let location = self.get_location(offset);
+        format!("{}:{}:{}", self.get_name(location.file), location.line, location.column)
+    }
+
+    // For printing diagnostics with codespan: the file and the span local to it.
+    pub(crate) fn to_codespan(&self, span: Span) -> (codespan::FileId, codespan::Span) {
+        let file = &self.files[self.lookup_file(span.0)];
+        let local = |offset: usize| (offset.saturating_sub(file.start)) as u32;
+        (file.codespan_id, codespan::Span::new(local(span.0), local(span.1)))
+    }
+
+    pub(crate) fn get_file_db(&self) -> &codespan::Files { &self.file_db }
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        SourceMap::new()
+    }
+}
@@ -1,5 +1,6 @@
 
-pub fn remove_comments(text: &str) -> String {
+// Returns byte ranges of comments, strings are taken into account.
+fn find_comments(text: &str) -> Vec<(usize, usize)> {
     #[derive(Debug)]
     enum PrimaryState {
         InCode,
@@ -16,8 +17,9 @@ pub fn remove_comments(text: &str) -> String {
     };
     let mut s1 = PrimaryState::InCode;
     let mut s2 = SecondaryState::NotInString;
-    let mut output = String::new();
-    for ch in text.chars() {
+    let mut comments = Vec::new();
+    let mut comment_start = 0;
+    for (i, ch) in text.char_indices() {
         match (&s2, ch) {
             (SecondaryState::NotInString, '"')      => s2 = SecondaryState::InString,
             (SecondaryState::InString, '\\') => s2 = SecondaryState::InStringAfterEscape,
@@ -28,28 +30,107 @@ pub fn remove_comments(text: &str) -> String {
 
         if let SecondaryState::NotInString = s2 {
             match (&s1, ch) {
-                (PrimaryState::InCode, '#') => s1 = PrimaryState::InSingleLineComment,
-                (PrimaryState::InCode, '/') => s1 = PrimaryState::AfterForwardSlash,
+                (PrimaryState::InCode, '#') => {
+                    s1 = PrimaryState::InSingleLineComment;
+                    comment_start = i;
+                },
+                (PrimaryState::InCode, '/') => {
+                    s1 = PrimaryState::AfterForwardSlash;
+                    comment_start = i;
+                },
                 (PrimaryState::AfterForwardSlash, '/') => s1 = PrimaryState::InSingleLineComment,
                 (PrimaryState::AfterForwardSlash, '*') => s1 = PrimaryState::InMultiLineComment,
-                (PrimaryState::AfterForwardSlash, _) => {
+                (PrimaryState::AfterForwardSlash, _) => s1 = PrimaryState::InCode,
+                (PrimaryState::InSingleLineComment, '\n') => {
                     s1 = PrimaryState::InCode;
-                    output.push('/');
-                }
-                (PrimaryState::InSingleLineComment, '\n') => s1 = PrimaryState::InCode,
+                    comments.push((comment_start, i));
+                },
                 (PrimaryState::InMultiLineComment, '*') => s1 = PrimaryState::InMultiLineAfterAsterisk,
                 (PrimaryState::InMultiLineAfterAsterisk, '/') => {
                     s1 = PrimaryState::InCode;
-                    continue;
+                    comments.push((comment_start, i + 1));
                 },
                 (PrimaryState::InMultiLineAfterAsterisk, _) => s1 = PrimaryState::InMultiLineComment,
                 (_,_) => (),
             }
         }
-        output.push(match s1 {
-            PrimaryState::InCode => ch,
-            _ => if ch == '\n' { '\n' } else { ' ' },
-        });
     }
+    match s1 {
+        PrimaryState::InCode | PrimaryState::AfterForwardSlash => (),
+        _ => comments.push((comment_start, text.len())),
+    }
+    return comments;
+}
+
+// Replaces comments with whitespace, byte offsets of the code are kept intact,
+// so that spans point to the same place in the original text.
+pub fn remove_comments(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in find_comments(text) {
+        output.push_str(&text[last..start]);
+        for ch in text[start..end].chars() {
+            match ch {
+                '\n' => output.push('\n'),
+                _ => output.extend(std::iter::repeat(' ').take(ch.len_utf8())),
+            }
+        }
+        last = end;
+    }
+    output.push_str(&text[last..]);
     return output;
-}
\ No newline at end of file
+}
+
+// Returns comments with their byte offsets, single line comments don't include the line break.
+pub fn extract_comments(text: &str) -> Vec<(usize, String)> {
+    find_comments(text).into_iter().map(|(start, end)| (start, text[start..end].to_owned())).collect()
+}
+
+// Resolves escape sequences of a string literal body (without quotes).
+pub fn unescape_string(text: &str) -> String {
+    let mut output = String::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            output.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('"') => output.push('"'),
+            Some('\\') => output.push('\\'),
+            // unknown escape sequences are kept as they are
+            Some(other) => {
+                output.push('\\');
+                output.push(other);
+            },
+            None => output.push('\\'),
+        }
+    }
+    return output;
+}
+
+// Inverse of unescape_string.
+pub fn escape_string(text: &str) -> String {
+    let mut output = String::new();
+    for ch in text.chars() {
+        match ch {
+            '\\' => output.push_str("\\\\"),
+            '"' => output.push_str("\\\""),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            _ => output.push(ch),
+        }
+    }
+    return output;
+}
+
+// Whether the file exists and was modified not earlier than the other one, which must exist too.
+pub fn is_newer(path: &std::path::Path, than: &std::path::Path) -> bool {
+    let modified = |path: &std::path::Path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    match (modified(path), modified(than)) {
+        (Some(time), Some(other_time)) => time >= other_time,
+        _ => false,
+    }
+}
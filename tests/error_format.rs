@@ -0,0 +1,48 @@
+// Runs latte with --error-format=json on a file with an error and a warning.
+use serde_json::Value;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn json_diagnostics() {
+    let dir = std::env::temp_dir().join(format!("latte_error_format_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("unreachable.lat");
+    fs::write(&path, "int main() {\n  return 0;\n  printInt(x);\n}\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_latte"))
+        .arg("--error-format=json")
+        .arg(&path)
+        .output()
+        .expect("cannot start latte");
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    // the grader reads only the first line of stderr, diagnostics are on stdout
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().next(), Some("ERROR"));
+    assert!(stderr.lines().skip(1).all(|line| line.is_empty()), "{}", stderr);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let diags: Vec<Value> = stdout.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(diags.len(), 2, "{}", stdout);
+
+    let error = &diags[0];
+    assert_eq!((error["code"].as_str(), error["severity"].as_str()), (Some("E0005"), Some("error")));
+    assert_eq!(error["message"], "undeclared variable x");
+    let labels = error["labels"].as_array().unwrap();
+    assert_eq!(labels.len(), 1);
+    let label = &labels[0];
+    assert_eq!(label["file"], path.to_str().unwrap());
+    assert_eq!(label["message"], "in this expression");
+    assert_eq!((label["byte_start"].as_u64(), label["byte_end"].as_u64()), (Some(36), Some(37)));
+    assert_eq!((label["line_start"].as_u64(), label["column_start"].as_u64()), (Some(3), Some(12)));
+    assert_eq!((label["line_end"].as_u64(), label["column_end"].as_u64()), (Some(3), Some(13)));
+
+    let warning = &diags[1];
+    assert_eq!((warning["code"].as_str(), warning["severity"].as_str()), (Some("W0001"), Some("warning")));
+    assert_eq!(warning["message"], "unreachable code");
+    let label = &warning["labels"][0];
+    assert_eq!((label["byte_start"].as_u64(), label["byte_end"].as_u64()), (Some(27), Some(39)));
+    assert_eq!((label["line_start"].as_u64(), label["column_start"].as_u64()), (Some(3), Some(3)));
+    assert_eq!((label["line_end"].as_u64(), label["column_end"].as_u64()), (Some(3), Some(15)));
+}
@@ -0,0 +1,178 @@
+// Drives latte-lsp with a scripted client.
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: i64,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_latte-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("cannot start latte-lsp");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Client {child, stdin, stdout, next_id: 1}
+    }
+
+    fn send(&mut self, message: Value) {
+        let content = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", content.len(), content).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+        let mut content = vec![0; content_length];
+        self.stdout.read_exact(&mut content).unwrap();
+        serde_json::from_slice(&content).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        let response = self.receive();
+        assert_eq!(response["id"], id);
+        response
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    fn open(&mut self, uri: &str, text: &str) -> Value {
+        self.notify("textDocument/didOpen", json!({
+            "textDocument": {"uri": uri, "languageId": "latte", "version": 1, "text": text}
+        }));
+        let notification = self.receive();
+        assert_eq!(notification["method"], "textDocument/publishDiagnostics");
+        notification["params"]["diagnostics"].clone()
+    }
+
+    fn at(&mut self, method: &str, uri: &str, line: u64, character: u64) -> Value {
+        self.request(method, json!({
+            "textDocument": {"uri": uri},
+            "position": {"line": line, "character": character},
+        }))["result"].clone()
+    }
+
+    fn stop(mut self) {
+        let response = self.request("shutdown", Value::Null);
+        assert_eq!(response["result"], Value::Null);
+        self.notify("exit", Value::Null);
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+const PROGRAM: &str = "\
+int twice(int n) {
+    return 2 * n;
+}
+
+int main() {
+    int x = 21;
+    printInt(twice(x));
+    return 0;
+}
+";
+
+#[test]
+fn diagnostics() {
+    let mut client = Client::start();
+    client.request("initialize", json!({"capabilities": {}}));
+    client.notify("initialized", json!({}));
+
+    let diags = client.open("file:///bad.lat", "int main() {\n    x = 1;\n    return 0;\n}\n");
+    assert_eq!(diags.as_array().unwrap().len(), 1);
+    assert_eq!(diags[0]["code"], "E0005");
+    assert_eq!(diags[0]["severity"], 1);
+    assert_eq!(diags[0]["range"]["start"], json!({"line": 1, "character": 4}));
+
+    client.notify("textDocument/didChange", json!({
+        "textDocument": {"uri": "file:///bad.lat", "version": 2},
+        "contentChanges": [{"text": PROGRAM}],
+    }));
+    let notification = client.receive();
+    assert_eq!(notification["params"]["diagnostics"], json!([]));
+
+    client.stop();
+}
+
+#[test]
+fn hover_definition_completion() {
+    let mut client = Client::start();
+    client.request("initialize", json!({"capabilities": {}}));
+    let diags = client.open("file:///good.lat", PROGRAM);
+    assert_eq!(diags, json!([]));
+
+    // `twice` in the call
+    let hover = client.at("textDocument/hover", "file:///good.lat", 6, 14);
+    assert_eq!(hover["contents"]["value"], "int twice(int)");
+    // `x` in the call
+    let hover = client.at("textDocument/hover", "file:///good.lat", 6, 19);
+    assert_eq!(hover["contents"]["value"], "x: int");
+    // `2 * n` has a known type but not a known value
+    let hover = client.at("textDocument/hover", "file:///good.lat", 1, 13);
+    assert_eq!(hover["contents"]["value"], "int");
+
+    let definition = client.at("textDocument/definition", "file:///good.lat", 6, 19);
+    assert_eq!(definition["range"]["start"], json!({"line": 5, "character": 8}));
+    let definition = client.at("textDocument/definition", "file:///good.lat", 1, 15);
+    assert_eq!(definition["range"]["start"], json!({"line": 0, "character": 14}));
+    let definition = client.at("textDocument/definition", "file:///good.lat", 6, 14);
+    assert_eq!(definition["range"]["start"], json!({"line": 0, "character": 0}));
+
+    let completion = client.at("textDocument/completion", "file:///good.lat", 7, 4);
+    let labels: Vec<&str> = completion.as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+    assert!(labels.contains(&"x"));
+    assert!(labels.contains(&"twice"));
+    assert!(labels.contains(&"printString"));
+    assert!(!labels.contains(&"n"));
+
+    let response = client.request("textDocument/formatting", json!({}));
+    assert_eq!(response["error"]["code"], -32601);
+
+    client.stop();
+}
+
+#[test]
+fn escaped_uris() {
+    // the directory name has spaces, they are %20 in URIs
+    let dir = std::env::temp_dir().join(format!("latte lsp {}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("lib.lat"), "export int twice(int n) {\n  return 2 * n;\n}\n").unwrap();
+    let dir = std::fs::canonicalize(&dir).unwrap();
+    let uri = |name: &str| format!("file://{}/{}", dir.display().to_string().replace(' ', "%20"), name);
+
+    let mut client = Client::start();
+    client.request("initialize", json!({"capabilities": {}}));
+    // the import is found only if the path of the document is decoded
+    let diags = client.open(&uri("main.lat"), "import \"lib.lat\";\nint main() {\n  printInt(twice(1));\n  return 0;\n}\n");
+    assert_eq!(diags, json!([]));
+    let definition = client.at("textDocument/definition", &uri("main.lat"), 2, 11);
+    assert_eq!(definition["uri"], uri("lib.lat"));
+    // after `export `
+    assert_eq!(definition["range"]["start"], json!({"line": 0, "character": 7}));
+
+    client.stop();
+    std::fs::remove_dir_all(&dir).unwrap();
+}